@@ -6,6 +6,7 @@ use raylib::{
 pub struct Assets {
     pub ship: Texture2D,
     pub ship_dead: Texture2D,
+    pub turret: Texture2D,
     pub bullet: Texture2D,
     pub rock: Texture2D,
 }
@@ -19,6 +20,7 @@ pub fn load(rl: &mut RaylibHandle, thread: &RaylibThread) -> Assets {
     Assets {
         ship: load_texture("assets/ship.png", rl, thread),
         ship_dead: load_texture("assets/ship_dead.png", rl, thread),
+        turret: load_texture("assets/turret.png", rl, thread),
         bullet: load_texture("assets/bullet.png", rl, thread),
         rock: load_texture("assets/rock.png", rl, thread),
     }
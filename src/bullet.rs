@@ -4,20 +4,22 @@ use raylib::{
     color::Color,
     drawing::RaylibDraw,
     math::{Rectangle, Vector2},
-    prelude::{RaylibDrawHandle, RaylibHandle},
+    prelude::RaylibDrawHandle,
 };
 
 use crate::{
     assets::Assets,
-    collision::{Circle, CollisionFrame, CollisionLayer},
+    collision::{CollisionFrame, CollisionLayer, Hit, Shape},
     object::Object,
 };
 
 pub struct Bullet {
     pos: Vector2,
+    prev_pos: Vector2,
     rotation: f32,
     lifetime: f32,
     sleep_queued: bool,
+    owner: usize,
 }
 
 impl Bullet {
@@ -27,33 +29,61 @@ impl Bullet {
     fn new() -> Self {
         Self {
             pos: Vector2::zero(),
+            prev_pos: Vector2::zero(),
             rotation: 0.0,
             lifetime: Self::LIFETIME,
             sleep_queued: false,
+            owner: 0,
         }
     }
 }
 
 impl Object for Bullet {
-    fn update(&mut self, rl: &RaylibHandle, collision_frame: &CollisionFrame) {
-        self.lifetime -= rl.get_frame_time();
-
-        if collision_frame.check_collision(vec!["rock", "bullet"], self.get_shape()) {
-            self.sleep_queued = true
-        }
+    fn update(&mut self, dt: f32, _collision_frame: &CollisionFrame) {
+        self.prev_pos = self.pos;
+        self.lifetime -= dt;
 
         self.pos += Vector2::new(
             self.rotation.to_radians().cos(),
             self.rotation.to_radians().sin(),
         ) * Self::SPEED
-            * rl.get_frame_time();
+            * dt;
 
         if self.lifetime <= 0.0 {
             self.sleep_queued = true
         }
     }
 
-    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets) {
+    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, alpha: f32) {
+        BulletSnapshot {
+            pos: self.prev_pos.lerp(self.pos, alpha),
+            rotation: self.rotation,
+            owner: self.owner,
+        }
+        .draw(d, assets)
+    }
+
+    fn get_shape(&self) -> Shape {
+        Shape::Circle(self.pos, 10.0)
+    }
+
+    fn get_prev_pos(&self) -> Vector2 {
+        self.prev_pos
+    }
+
+    fn get_owner(&self) -> Option<usize> {
+        Some(self.owner)
+    }
+}
+
+pub struct BulletSnapshot {
+    pub pos: Vector2,
+    pub rotation: f32,
+    pub owner: usize,
+}
+
+impl BulletSnapshot {
+    pub fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets) {
         d.draw_texture_pro(
             &assets.bullet,
             Rectangle::new(0.0, 0.0, 50.0, 50.0),
@@ -63,19 +93,17 @@ impl Object for Bullet {
             Color::RED,
         );
     }
-
-    fn get_shape(&self) -> Circle {
-        (self.pos, 10.0)
-    }
 }
 
 pub struct BulletPool {
     asleep: Vec<Bullet>,
     pub awake: VecDeque<Bullet>,
+    friendly_fire: bool,
 }
 
 impl BulletPool {
-    pub fn new(count: usize) -> Self {
+    /// With `friendly_fire` off, bullets pass through their shooter and its other bullets.
+    pub fn new(count: usize, friendly_fire: bool) -> Self {
         let mut asleep = Vec::new();
         for _ in 0..count {
             asleep.push(Bullet::new());
@@ -84,19 +112,36 @@ impl BulletPool {
         Self {
             asleep,
             awake: VecDeque::new(),
+            friendly_fire,
         }
     }
 
-    pub fn shoot(&mut self, pos: Vector2, rotation: f32) -> Result<(), String> {
-        let mut bullet = self
-            .asleep
-            .pop()
-            .ok_or(String::from("Ran out of bullets."))?;
+    /// Wakes a bullet, growing the pool if every bullet is already in flight.
+    pub fn shoot(&mut self, pos: Vector2, rotation: f32, owner: usize) {
+        let mut bullet = self.asleep.pop().unwrap_or_else(Bullet::new);
         bullet.pos = pos;
+        bullet.prev_pos = pos;
         bullet.rotation = rotation;
-        self.awake.push_front(bullet);
+        bullet.owner = owner;
+        // Added at the back so bullets already in flight keep their index in this tick's
+        // collision frame.
+        self.awake.push_back(bullet);
+    }
 
-        Ok(())
+    pub fn snapshot(&self) -> Vec<BulletSnapshot> {
+        self.awake
+            .iter()
+            .map(|bullet| BulletSnapshot {
+                pos: bullet.pos,
+                rotation: bullet.rotation,
+                owner: bullet.owner,
+            })
+            .collect()
+    }
+
+    /// Whether a bullet can hit `other`.
+    pub fn can_hit(&self, bullet: &Hit, other: &Hit) -> bool {
+        self.friendly_fire || bullet.owner != other.owner
     }
 
     pub fn collision_layer(&mut self) -> CollisionLayer {
@@ -105,11 +150,14 @@ impl BulletPool {
 }
 
 impl Object for BulletPool {
-    fn update(&mut self, rl: &RaylibHandle, collision_frame: &CollisionFrame) {
+    fn update(&mut self, dt: f32, collision_frame: &CollisionFrame) {
         let mut sleep = Vec::new();
 
         for (i, obj) in self.awake.iter_mut().enumerate() {
-            obj.update(rl, collision_frame);
+            obj.update(dt, collision_frame);
+            if collision_frame.contacts("bullet", i).next().is_some() {
+                obj.sleep_queued = true;
+            }
             if obj.sleep_queued {
                 obj.sleep_queued = false;
                 obj.lifetime = Bullet::LIFETIME;
@@ -124,14 +172,14 @@ impl Object for BulletPool {
         }
     }
 
-    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets) {
-        self.awake.draw(d, assets)
+    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, alpha: f32) {
+        self.awake.draw(d, assets, alpha)
     }
 
     fn is_colliding(&self, other: &dyn Object) -> bool {
         self.awake.is_colliding(other)
     }
-    fn get_shape(&self) -> Circle {
-        (Vector2::zero(), 0.0)
+    fn get_shape(&self) -> Shape {
+        Shape::empty()
     }
 }
@@ -1,112 +1,553 @@
 use std::collections::HashMap;
 
-use raylib::{
-    collision,
-    math::{Rectangle, Vector2},
-};
+use raylib::math::{Rectangle, Vector2};
 
 use crate::object::Object;
 
+pub use self::shape::{time_of_impact, Shape};
+
+mod shape;
+
 pub type Circle = (Vector2, f32);
 
-pub fn check_collision_circles(circle1: Circle, circle2: Circle) -> bool {
-    if circle1.1 == 0.0 || circle2.1 == 0.0 || circle1 == circle2 {
-        false
-    } else {
-        collision::check_collision_circles(circle1.0, circle1.1, circle2.0, circle2.1)
+/// The playable area. Ships stop at its edges and bullets that reach them are gone.
+pub fn arena() -> Rectangle {
+    Rectangle::new(0.0, 0.0, 1280.0, 960.0)
+}
+
+/// Where `circle` has to be to sit fully inside the arena, and whether it had to be moved.
+pub fn keep_in_arena(circle: Circle) -> (Vector2, bool) {
+    let (pos, radius) = circle;
+    let arena = arena();
+    let clamped = Vector2::new(
+        pos.x
+            .clamp(arena.x + radius, arena.x + arena.width - radius),
+        pos.y
+            .clamp(arena.y + radius, arena.y + arena.height - radius),
+    );
+    (clamped, clamped != pos)
+}
+
+/// Two objects that touched over the last tick, by their index within their layers.
+#[derive(Clone, Copy)]
+pub struct CollisionEvent {
+    pub entity_a: usize,
+    pub entity_b: usize,
+    pub layer_a: &'static str,
+    pub layer_b: &'static str,
+}
+
+/// What a shape ran into.
+pub struct Hit {
+    pub shape: Shape,
+    /// The ship responsible for the object that was hit, if any.
+    pub owner: Option<usize>,
+}
+
+/// Buckets shapes by the cells their bounding box touches, so lookups only have to look at
+/// shapes near what they're checking instead of every shape in the layer.
+struct Grid {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Grid {
+    const CELL_SIZE: f32 = 64.0;
+
+    fn new() -> Self {
+        Grid {
+            cells: HashMap::new(),
+        }
+    }
+
+    /// The first and last cell on each axis covered by `shape` moving from being centered on
+    /// `from` to where it is.
+    fn bounds(from: Vector2, shape: &Shape) -> ((i32, i32), (i32, i32)) {
+        let bounds = shape.bounds();
+        let motion = shape.center() - from;
+        let cell = |v: f32| (v / Grid::CELL_SIZE).floor() as i32;
+        (
+            (
+                cell(bounds.x.min(bounds.x - motion.x)),
+                cell((bounds.x + bounds.width).max(bounds.x + bounds.width - motion.x)),
+            ),
+            (
+                cell(bounds.y.min(bounds.y - motion.y)),
+                cell((bounds.y + bounds.height).max(bounds.y + bounds.height - motion.y)),
+            ),
+        )
+    }
+
+    fn cells_around(from: Vector2, shape: &Shape) -> impl Iterator<Item = (i32, i32)> {
+        let ((x0, x1), (y0, y1)) = Grid::bounds(from, shape);
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
     }
+
+    fn cell_count(from: Vector2, shape: &Shape) -> usize {
+        let ((x0, x1), (y0, y1)) = Grid::bounds(from, shape);
+        (x1 - x0 + 1) as usize * (y1 - y0 + 1) as usize
+    }
+
+    fn insert(&mut self, index: usize, from: Vector2, shape: &Shape) {
+        for cell in Grid::cells_around(from, shape) {
+            self.cells.entry(cell).or_default().push(index);
+        }
+    }
+
+    /// The non-empty cells a ray from `origin` in the direction `dir` passes through within
+    /// `length`, in order, along with how far along the ray it enters each one.
+    fn walk(
+        &self,
+        origin: Vector2,
+        dir: Vector2,
+        length: f32,
+    ) -> impl Iterator<Item = (f32, &Vec<usize>)> + '_ {
+        let cell = |v: f32| (v / Grid::CELL_SIZE).floor() as i32;
+        // Distance along the ray to the first cell boundary on an axis, and between boundaries.
+        let axis = |pos: f32, dir: f32, cell: i32| {
+            if dir > 0.0 {
+                (
+                    ((cell + 1) as f32 * Grid::CELL_SIZE - pos) / dir,
+                    Grid::CELL_SIZE / dir,
+                    1,
+                )
+            } else if dir < 0.0 {
+                (
+                    (cell as f32 * Grid::CELL_SIZE - pos) / dir,
+                    -Grid::CELL_SIZE / dir,
+                    -1,
+                )
+            } else {
+                (f32::INFINITY, f32::INFINITY, 0)
+            }
+        };
+
+        let (mut x, mut y) = (cell(origin.x), cell(origin.y));
+        let (mut next_x, delta_x, step_x) = axis(origin.x, dir.x, x);
+        let (mut next_y, delta_y, step_y) = axis(origin.y, dir.y, y);
+        let mut t = 0.0;
+
+        std::iter::from_fn(move || {
+            while t <= length {
+                let current = (t, (x, y));
+                if next_x < next_y {
+                    t = next_x;
+                    x += step_x;
+                    next_x += delta_x;
+                } else {
+                    t = next_y;
+                    y += step_y;
+                    next_y += delta_y;
+                }
+                if let Some(indices) = self.cells.get(&current.1) {
+                    return Some((current.0, indices));
+                }
+            }
+            None
+        })
+    }
+
+    /// Indices of the shapes that might touch `shape` on its way from `from`, in the order
+    /// they were inserted.
+    fn query(&self, from: Vector2, shape: &Shape) -> Vec<usize> {
+        let mut indices: Vec<usize> = Grid::cells_around(from, shape)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+}
+
+/// Where a ray first ran into something.
+#[derive(Clone)]
+pub struct RayHit {
+    /// The name of the layer that was hit, `"wall"` for the edge of the arena or `"none"`.
+    pub kind: &'static str,
+    pub distance: f32,
+    pub point: Vector2,
+    /// The surface normal at `point`, facing back along the ray.
+    pub normal: Vector2,
+    /// The index of the object that was hit within its layer.
+    pub id: Option<usize>,
+}
+
+impl RayHit {
+    /// Stands in for a ray that hasn't been cast yet.
+    pub fn none() -> Self {
+        RayHit {
+            kind: "none",
+            distance: -1.0,
+            point: Vector2::zero(),
+            normal: Vector2::zero(),
+            id: None,
+        }
+    }
+}
+
+struct Entry {
+    shape: Shape,
+    /// Where the shape was centered at the start of the last tick.
+    from: Vector2,
+    owner: Option<usize>,
 }
 
 pub struct CollisionLayer {
-    shapes: Vec<Circle>,
+    shapes: Vec<Entry>,
+    grid: Grid,
 }
 
 impl CollisionLayer {
     pub fn from(collection: &[impl Object]) -> Self {
-        let mut s = CollisionLayer { shapes: Vec::new() };
+        let mut s = CollisionLayer {
+            shapes: Vec::new(),
+            grid: Grid::new(),
+        };
         for obj in collection {
-            s.shapes.push(obj.get_shape());
+            let entry = Entry {
+                shape: obj.get_shape(),
+                from: obj.get_prev_pos(),
+                owner: obj.get_owner(),
+            };
+            if !entry.shape.is_empty() {
+                s.grid.insert(s.shapes.len(), entry.from, &entry.shape);
+            }
+            s.shapes.push(entry);
+        }
+        s
+    }
+
+    /// The walls around the arena, as slabs running along the outside of each edge. They're
+    /// thick enough that anything that ends up past an edge is inside one.
+    fn walls() -> Self {
+        const THICKNESS: f32 = 100.0;
+        let arena = arena();
+        let slabs = [
+            Rectangle::new(
+                arena.x - THICKNESS,
+                arena.y - THICKNESS,
+                THICKNESS,
+                arena.height + THICKNESS * 2.0,
+            ),
+            Rectangle::new(
+                arena.x + arena.width,
+                arena.y - THICKNESS,
+                THICKNESS,
+                arena.height + THICKNESS * 2.0,
+            ),
+            Rectangle::new(arena.x, arena.y - THICKNESS, arena.width, THICKNESS),
+            Rectangle::new(arena.x, arena.y + arena.height, arena.width, THICKNESS),
+        ];
+
+        let mut s = CollisionLayer {
+            shapes: Vec::new(),
+            grid: Grid::new(),
+        };
+        for slab in slabs {
+            let shape = Shape::Rect(slab);
+            let from = shape.center();
+            s.grid.insert(s.shapes.len(), from, &shape);
+            s.shapes.push(Entry {
+                shape,
+                from,
+                owner: None,
+            });
         }
         s
     }
 
-    pub fn check_collision(&self, circle: Circle) -> bool {
-        for shape in &self.shapes {
-            if check_collision_circles(*shape, circle) {
-                return true;
+    fn hit(&self, i: usize) -> Hit {
+        Hit {
+            shape: self.shapes[i].shape.clone(),
+            owner: self.shapes[i].owner,
+        }
+    }
+
+    /// The nearest shape a ray from `origin` in the direction `dir` enters within `length`,
+    /// as the distance along the ray, the surface normal there and the shape's index.
+    fn cast(&self, origin: Vector2, dir: Vector2, length: f32) -> Option<(f32, Vector2, usize)> {
+        let mut nearest: Option<(f32, Vector2, usize)> = None;
+        for (t, indices) in self.grid.walk(origin, dir, length) {
+            // Anything further along can only be in cells the walk hasn't reached yet.
+            if nearest.is_some_and(|(distance, _, _)| distance <= t) {
+                break;
             }
+            for &i in indices {
+                let Some((distance, normal)) = self.shapes[i].shape.raycast(origin, dir, length)
+                else {
+                    continue;
+                };
+                if nearest.is_none_or(|(d, _, j)| (distance, i) < (d, j)) {
+                    nearest = Some((distance, normal, i));
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Indices of the shapes that might touch `shape` on its way from `from`. Empty shapes
+    /// never collide, so they're left out.
+    fn near(&self, from: Vector2, shape: &Shape) -> Vec<usize> {
+        // Past a point, going through the grid costs more than just checking everything.
+        if Grid::cell_count(from, shape) >= self.shapes.len() {
+            return (0..self.shapes.len())
+                .filter(|&i| !self.shapes[i].shape.is_empty())
+                .collect();
         }
-        false
+        self.grid.query(from, shape)
     }
 }
 
 pub struct CollisionFrame {
     layers: HashMap<&'static str, CollisionLayer>,
+    contacts: Vec<CollisionEvent>,
 }
 
 impl CollisionFrame {
     const MAX_RAY_LENGTH: f32 = 1000.0;
 
+    /// Every frame has the walls around the arena as its `"wall"` layer on top of `layers`.
     pub fn new(layers: Vec<(&'static str, CollisionLayer)>) -> Self {
+        let mut layers: HashMap<_, _> = layers.into_iter().collect();
+        layers.insert("wall", CollisionLayer::walls());
         CollisionFrame {
-            layers: layers.into_iter().collect(),
+            layers,
+            contacts: Vec::new(),
         }
     }
 
-    pub fn check_collision(&self, mut layers: Vec<&str>, circle: Circle) -> bool {
-        while !layers.is_empty() {
-            let Some(layer) = self.layers.get(layers.pop().unwrap()) else {
+    /// Swaps in a new `layer` for objects that moved since the frame was built, so their moves
+    /// can be swept too. Contacts found so far are kept.
+    pub fn replace_layer(&mut self, name: &'static str, layer: CollisionLayer) {
+        self.layers.insert(name, layer);
+    }
+
+    /// Works out what everything in `layer` ran into over the last tick. Each of them is
+    /// stopped by the first thing in `against` that it touched and `filter` accepts, so it
+    /// only ever makes one contact. Contacts between two shapes in `layer` stop both of them.
+    /// Collisions count anything passed through along the way, not just what overlaps at the
+    /// end, so fast objects can't skip through each other.
+    pub fn resolve_contacts(
+        &mut self,
+        layer: &'static str,
+        against: Vec<&'static str>,
+        filter: impl Fn(&Hit, &Hit) -> bool,
+    ) {
+        let Some(movers) = self.layers.get(layer) else {
+            return;
+        };
+
+        let mut candidates = Vec::new();
+        for (i, mover) in movers.shapes.iter().enumerate() {
+            for &name in &against {
+                let Some(other) = self.layers.get(name) else {
+                    continue;
+                };
+                for j in other.near(mover.from, &mover.shape) {
+                    if name == layer && i == j {
+                        continue;
+                    }
+                    let entry = &other.shapes[j];
+                    let Some(t) =
+                        time_of_impact(mover.from, &mover.shape, entry.from, &entry.shape)
+                    else {
+                        continue;
+                    };
+                    if filter(&movers.hit(i), &other.hit(j)) {
+                        candidates.push((t, i, name, j));
+                    }
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut stopped = vec![false; movers.shapes.len()];
+        for (_, i, name, j) in candidates {
+            if stopped[i] || (name == layer && stopped[j]) {
                 continue;
-            };
-            if layer.check_collision(circle) {
-                return true;
             }
+            stopped[i] = true;
+            if name == layer {
+                stopped[j] = true;
+            }
+            self.contacts.push(CollisionEvent {
+                entity_a: i,
+                entity_b: j,
+                layer_a: layer,
+                layer_b: name,
+            });
         }
-        false
     }
 
-    pub fn raycast(
-        &self,
-        layers: Vec<&str>,
-        pos: Vector2,
-        rotation: f32,
-        radius: f32,
-    ) -> (String, f32) {
-        self.raycast_step(layers, pos, rotation, radius, 0.0)
+    /// Everything the object at index `id` in `layer` made contact with, along with the name
+    /// of its layer.
+    pub fn contacts<'a>(
+        &'a self,
+        layer: &'a str,
+        id: usize,
+    ) -> impl Iterator<Item = (&'static str, Hit)> + 'a {
+        self.contacts.iter().filter_map(move |contact| {
+            let (name, other) = if contact.layer_a == layer && contact.entity_a == id {
+                (contact.layer_b, contact.entity_b)
+            } else if contact.layer_b == layer && contact.entity_b == id {
+                (contact.layer_a, contact.entity_a)
+            } else {
+                return None;
+            };
+            Some((name, self.layers[name].hit(other)))
+        })
     }
 
-    fn raycast_step(
+    /// Everything in `layers` whose edge is within `range` of `pos` and whose center is within
+    /// `fov / 2` degrees either side of `rotation`, along with the name of its layer and its
+    /// distance from `pos`. `filter` is given the layer name too.
+    pub fn scan(
         &self,
         layers: Vec<&str>,
         pos: Vector2,
         rotation: f32,
-        radius: f32,
-        dist: f32,
-    ) -> (String, f32) {
-        let mut mut_layers = layers.clone();
-        while !mut_layers.is_empty() {
-            let name = mut_layers.pop().unwrap();
-            let Some(layer) = self.layers.get(name) else {
+        fov: f32,
+        range: f32,
+        filter: impl Fn(&str, &Hit) -> bool,
+    ) -> Vec<(&'static str, Hit, f32)> {
+        let mut hits = Vec::new();
+        for name in layers {
+            let Some((&name, layer)) = self.layers.get_key_value(name) else {
+                continue;
+            };
+            for i in layer.near(pos, &Shape::Circle(pos, range)) {
+                let hit = layer.hit(i);
+                let distance = hit.shape.distance_to(pos);
+                let offset = hit.shape.center() - pos;
+                let bearing = (offset.y.atan2(offset.x).to_degrees() - rotation + 540.0)
+                    .rem_euclid(360.0)
+                    - 180.0;
+                if distance <= range && bearing.abs() <= fov / 2.0 && filter(name, &hit) {
+                    hits.push((name, hit, distance));
+                }
+            }
+        }
+        hits
+    }
+
+    /// Shortcut for [`CollisionFrame::cast`] that only gives back what was hit and how far away.
+    pub fn raycast(&self, layers: Vec<&str>, pos: Vector2, rotation: f32) -> (String, f32) {
+        let hit = self.cast(layers, pos, rotation);
+        (String::from(hit.kind), hit.distance)
+    }
+
+    /// Casts a ray from `pos` towards `rotation` and finds the nearest shape in `layers` it
+    /// crosses, or the edge of the arena, up to [`CollisionFrame::MAX_RAY_LENGTH`] away.
+    pub fn cast(&self, layers: Vec<&str>, pos: Vector2, rotation: f32) -> RayHit {
+        let dir = Vector2::new(rotation.to_radians().cos(), rotation.to_radians().sin());
+
+        let mut nearest = RayHit {
+            kind: "none",
+            distance: CollisionFrame::MAX_RAY_LENGTH,
+            point: pos + dir * CollisionFrame::MAX_RAY_LENGTH,
+            normal: -dir,
+            id: None,
+        };
+        if let Some((distance, normal, _)) = self.layers["wall"].cast(pos, dir, nearest.distance) {
+            nearest = RayHit {
+                kind: "wall",
+                distance,
+                point: pos + dir * distance,
+                normal,
+                id: None,
+            };
+        }
+
+        for name in layers {
+            let Some((&name, layer)) = self.layers.get_key_value(name) else {
                 continue;
             };
-            if layer.check_collision((pos, radius)) {
-                return (String::from(name), dist + radius);
+            let Some((distance, normal, id)) = layer.cast(pos, dir, nearest.distance) else {
+                continue;
+            };
+            if distance < nearest.distance {
+                nearest = RayHit {
+                    kind: name,
+                    distance,
+                    point: pos + dir * distance,
+                    normal,
+                    id: Some(id),
+                };
+            }
+        }
+        nearest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rock::Rock;
+
+    #[test]
+    fn grid_walks_diagonal_ray() {
+        // A small shape in the middle of each cell of a 4x4 block, numbered by column.
+        let mut grid = Grid::new();
+        for x in 0..4 {
+            for y in 0..4 {
+                let center = Vector2::new(x as f32 + 0.5, y as f32 + 0.5) * Grid::CELL_SIZE;
+                grid.insert(x * 4 + y, center, &Shape::Circle(center, 1.0));
             }
         }
 
-        if dist > CollisionFrame::MAX_RAY_LENGTH {
-            (String::from("none"), dist)
-        } else if !Rectangle::new(0.0, 0.0, 1280.0, 960.0).check_collision_point_rec(pos) {
-            (String::from("wall"), dist)
-        } else {
-            self.raycast_step(
-                layers,
-                pos + Vector2::new(rotation.to_radians().cos(), rotation.to_radians().sin())
-                    * radius,
-                rotation,
-                radius,
-                dist + radius,
+        let dir = Vector2::new(1.0, 1.0).normalized();
+        let diagonal = 2f32.sqrt();
+        let walked: Vec<(f32, Vec<usize>)> = grid
+            .walk(
+                Vector2::new(8.0, 24.0),
+                dir,
+                4.0 * Grid::CELL_SIZE * diagonal,
             )
+            .map(|(t, indices)| (t, indices.clone()))
+            .collect();
+
+        // Crosses a horizontal line first, then alternates, and stops once it leaves the block.
+        let expected = [
+            (0.0, 0),
+            (40.0, 1),
+            (56.0, 5),
+            (104.0, 6),
+            (120.0, 10),
+            (168.0, 11),
+            (184.0, 15),
+        ];
+        assert_eq!(walked.len(), expected.len());
+        for ((t, indices), (offset, index)) in walked.iter().zip(expected) {
+            assert!((t - offset * diagonal).abs() < 1e-3, "{} != {}", t, offset);
+            assert_eq!(indices, &vec![index]);
         }
+
+        let short: Vec<f32> = grid
+            .walk(Vector2::new(8.0, 24.0), dir, 50.0)
+            .map(|(t, _)| t)
+            .collect();
+        assert_eq!(short, vec![0.0]);
+    }
+
+    #[test]
+    fn scan_measures_range_to_edges() {
+        let pos = Vector2::new(500.0, 500.0);
+        // The near one's center is out of range but its edge isn't. The far one is out of
+        // range entirely.
+        let rocks = vec![Rock::new(0, 630.0, 500.0), Rock::new(1, 700.0, 500.0)];
+        let near_edge = rocks[0].get_shape().distance_to(pos);
+        let range = 100.0;
+        assert!(near_edge < range && range < 130.0);
+        assert!(rocks[1].get_shape().distance_to(pos) > range);
+
+        let frame = CollisionFrame::new(vec![("rock", CollisionLayer::from(&rocks))]);
+        let found = frame.scan(vec!["rock"], pos, 0.0, 90.0, range, |_, _| true);
+
+        assert_eq!(found.len(), 1);
+        let (kind, hit, distance) = &found[0];
+        assert_eq!(*kind, "rock");
+        assert_eq!(hit.shape.center(), rocks[0].get_shape().center());
+        assert_eq!(*distance, near_edge);
     }
 }
@@ -0,0 +1,543 @@
+use raylib::math::{Rectangle, Vector2};
+
+/// The outline of something that can be collided with.
+#[derive(Clone, PartialEq)]
+pub enum Shape {
+    Circle(Vector2, f32),
+    Rect(Rectangle),
+    /// Corners of a convex outline, in order around it.
+    Polygon(Vec<Vector2>),
+    /// A line from one point to another, for thin obstacles. Nothing in the arena is built
+    /// from them yet.
+    #[allow(dead_code)]
+    Segment(Vector2, Vector2),
+}
+
+impl Shape {
+    /// A shape that never collides with anything, for objects that are gone.
+    pub fn empty() -> Self {
+        Shape::Circle(Vector2::zero(), 0.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Shape::Circle(_, radius) => *radius <= 0.0,
+            Shape::Rect(rect) => rect.width <= 0.0 || rect.height <= 0.0,
+            Shape::Polygon(points) => points.is_empty(),
+            Shape::Segment(..) => false,
+        }
+    }
+
+    pub fn center(&self) -> Vector2 {
+        match self {
+            Shape::Circle(center, _) => *center,
+            Shape::Rect(rect) => {
+                Vector2::new(rect.x + rect.width / 2.0, rect.y + rect.height / 2.0)
+            }
+            Shape::Polygon(points) => {
+                points
+                    .iter()
+                    .fold(Vector2::zero(), |sum, &point| sum + point)
+                    / points.len().max(1) as f32
+            }
+            Shape::Segment(start, end) => start.lerp(*end, 0.5),
+        }
+    }
+
+    /// The smallest axis-aligned rectangle the shape fits in.
+    pub fn bounds(&self) -> Rectangle {
+        let (points, radius) = self.hull();
+        let (min, max) = points.iter().fold(
+            (
+                Vector2::new(f32::INFINITY, f32::INFINITY),
+                Vector2::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+            ),
+            |(min, max), point| {
+                (
+                    Vector2::new(min.x.min(point.x), min.y.min(point.y)),
+                    Vector2::new(max.x.max(point.x), max.y.max(point.y)),
+                )
+            },
+        );
+        Rectangle::new(
+            min.x - radius,
+            min.y - radius,
+            max.x - min.x + radius * 2.0,
+            max.y - min.y + radius * 2.0,
+        )
+    }
+
+    /// How far `point` is from the edge of the shape, or `0` if it's inside.
+    pub fn distance_to(&self, point: Vector2) -> f32 {
+        let (points, radius) = self.hull();
+        (distance_to_hull(point, &points) - radius).max(0.0)
+    }
+
+    /// The point on the edge of the shape nearest to `point`, and the outward normal there.
+    pub fn surface_point(&self, point: Vector2) -> (Vector2, Vector2) {
+        let (points, radius) = self.hull();
+        let nearest = edges(&points)
+            .map(|(start, end)| closest_on_segment(point, start, end))
+            .min_by(|a, b| a.distance_to(point).total_cmp(&b.distance_to(point)))
+            .unwrap_or(points[0]);
+
+        let normal = if nearest == point && point == self.center() {
+            // The middle of a circle, where every way out is as short as any other.
+            Vector2::new(0.0, -1.0)
+        } else if nearest == point {
+            (point - self.center()).normalized()
+        } else if points.len() > 2 && inside_hull(point, &points) {
+            (nearest - point).normalized()
+        } else {
+            (point - nearest).normalized()
+        };
+        (nearest + normal * radius, normal)
+    }
+
+    /// Where along a ray from `origin` in the direction `dir` (a unit vector) it enters the
+    /// shape within `length`, and the surface normal there. A ray that starts inside the shape
+    /// hits it straight away.
+    pub fn raycast(&self, origin: Vector2, dir: Vector2, length: f32) -> Option<(f32, Vector2)> {
+        if self.is_empty() {
+            return None;
+        }
+        let (points, radius) = self.hull();
+        sweep_point(origin, dir, &points, radius).filter(|&(distance, _)| distance <= length)
+    }
+
+    /// Every shape is the convex hull of some points grown by a radius, which is what the
+    /// collision checks work with. Circles are a single point.
+    fn hull(&self) -> (Vec<Vector2>, f32) {
+        match self {
+            Shape::Circle(center, radius) => (vec![*center], *radius),
+            Shape::Rect(rect) => (
+                vec![
+                    Vector2::new(rect.x, rect.y),
+                    Vector2::new(rect.x + rect.width, rect.y),
+                    Vector2::new(rect.x + rect.width, rect.y + rect.height),
+                    Vector2::new(rect.x, rect.y + rect.height),
+                ],
+                0.0,
+            ),
+            Shape::Polygon(points) => (points.clone(), 0.0),
+            Shape::Segment(start, end) => (vec![*start, *end], 0.0),
+        }
+    }
+}
+
+/// When two shapes that moved in a straight line from being centered on `from1` and `from2`
+/// to where they are now over the same stretch of time first touched, as a fraction of that
+/// time. Shapes that haven't moved just have to overlap. Only circles are followed along the
+/// way, two shapes that aren't circles are checked where they end up.
+pub fn time_of_impact(
+    from1: Vector2,
+    shape1: &Shape,
+    from2: Vector2,
+    shape2: &Shape,
+) -> Option<f32> {
+    if shape1.is_empty() || shape2.is_empty() || (shape1 == shape2 && from1 == from2) {
+        return None;
+    }
+    let motion1 = shape1.center() - from1;
+    let motion2 = shape2.center() - from2;
+    let (points1, radius1) = shape1.hull();
+    let (points2, radius2) = shape2.hull();
+
+    // Track a circle from the other shape's point of view, which leaves a single point moving
+    // past the other shape grown by the circle's radius.
+    let (point, motion, points): (Vector2, Vector2, Vec<Vector2>) = if points1.len() == 1 {
+        (
+            points1[0] - motion1,
+            motion1 - motion2,
+            points2.iter().map(|&p| p - motion2).collect(),
+        )
+    } else if points2.len() == 1 {
+        (
+            points2[0] - motion2,
+            motion2 - motion1,
+            points1.iter().map(|&p| p - motion1).collect(),
+        )
+    } else {
+        return hulls_overlap(&points1, &points2).then_some(1.0);
+    };
+    sweep_point(point, motion, &points, radius1 + radius2)
+        .map(|(t, _)| t)
+        .filter(|&t| t <= 1.0)
+}
+
+/// When a point moving from `start` by `motion` each unit of time first touches the convex
+/// hull of `points` grown by `radius`, and the surface normal where it does.
+fn sweep_point(
+    start: Vector2,
+    motion: Vector2,
+    points: &[Vector2],
+    radius: f32,
+) -> Option<(f32, Vector2)> {
+    if distance_to_hull(start, points) <= radius {
+        return Some((0.0, -motion.normalized()));
+    }
+
+    let mut first: Option<(f32, Vector2)> = None;
+    let mut consider = |t: f32, normal: Vector2| {
+        if first.is_none_or(|(earliest, _)| t < earliest) {
+            first = Some((t, normal));
+        }
+    };
+
+    // Growing a hull by a radius rounds off its corners...
+    if radius > 0.0 {
+        for &corner in points {
+            if let Some(t) = sweep_point_circle(start, motion, corner, radius) {
+                consider(t, (start + motion * t - corner).normalized());
+            }
+        }
+    }
+    // ...and pushes its sides out.
+    for (a, b) in edges(points) {
+        let side = b - a;
+        let normal = Vector2::new(side.y, -side.x).normalized();
+        for normal in [normal, -normal] {
+            if motion.dot(normal) >= 0.0 {
+                continue;
+            }
+            let offset = normal * radius;
+            if let Some(t) = sweep_point_segment(start, motion, a + offset, b + offset) {
+                consider(t, normal);
+            }
+        }
+    }
+    first
+}
+
+/// When a point moving from `start` by `motion` each unit of time, starting outside the
+/// circle, enters it.
+fn sweep_point_circle(
+    start: Vector2,
+    motion: Vector2,
+    center: Vector2,
+    radius: f32,
+) -> Option<f32> {
+    let offset = start - center;
+    let a = motion.dot(motion);
+    let b = offset.dot(motion);
+    let c = offset.dot(offset) - radius * radius;
+    let discriminant = b * b - a * c;
+    if a == 0.0 || b >= 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some((-b - discriminant.sqrt()) / a)
+}
+
+/// When a point moving from `start` by `motion` each unit of time crosses the segment from
+/// `a` to `b`.
+fn sweep_point_segment(start: Vector2, motion: Vector2, a: Vector2, b: Vector2) -> Option<f32> {
+    let side = b - a;
+    let denominator = cross(motion, side);
+    if denominator == 0.0 {
+        return None;
+    }
+    let offset = a - start;
+    let t = cross(offset, side) / denominator;
+    let along = cross(offset, motion) / denominator;
+    (t >= 0.0 && (0.0..=1.0).contains(&along)).then_some(t)
+}
+
+/// Whether two convex hulls overlap, by looking for a gap between them along the normals of
+/// their sides.
+fn hulls_overlap(points1: &[Vector2], points2: &[Vector2]) -> bool {
+    let mut axes: Vec<Vector2> = edges(points1)
+        .chain(edges(points2))
+        .map(|(a, b)| Vector2::new(b.y - a.y, a.x - b.x))
+        .collect();
+    // Segments lying along the same line can only be told apart along it.
+    for points in [points1, points2] {
+        if points.len() == 2 {
+            axes.push(points[1] - points[0]);
+        }
+    }
+
+    axes.into_iter().all(|axis| {
+        let project = |points: &[Vector2]| {
+            points
+                .iter()
+                .map(|point| point.dot(axis))
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| {
+                    (min.min(d), max.max(d))
+                })
+        };
+        let (min1, max1) = project(points1);
+        let (min2, max2) = project(points2);
+        min1 <= max2 && min2 <= max1
+    })
+}
+
+/// The sides of a convex hull. A hull of two points has one side and a single point has none.
+fn edges(points: &[Vector2]) -> impl Iterator<Item = (Vector2, Vector2)> + '_ {
+    let count = match points.len() {
+        0 | 1 => 0,
+        2 => 1,
+        n => n,
+    };
+    (0..count).map(move |i| (points[i], points[(i + 1) % points.len()]))
+}
+
+fn inside_hull(point: Vector2, points: &[Vector2]) -> bool {
+    let sides: Vec<f32> = edges(points)
+        .map(|(a, b)| cross(b - a, point - a))
+        .collect();
+    sides.iter().all(|&side| side >= 0.0) || sides.iter().all(|&side| side <= 0.0)
+}
+
+fn distance_to_hull(point: Vector2, points: &[Vector2]) -> f32 {
+    if points.len() > 2 && inside_hull(point, points) {
+        return 0.0;
+    }
+    edges(points)
+        .map(|(a, b)| closest_on_segment(point, a, b).distance_to(point))
+        .fold(
+            points
+                .first()
+                .map_or(f32::INFINITY, |first| first.distance_to(point)),
+            f32::min,
+        )
+}
+
+fn closest_on_segment(point: Vector2, a: Vector2, b: Vector2) -> Vector2 {
+    let side = b - a;
+    let length = side.dot(side);
+    if length == 0.0 {
+        return a;
+    }
+    a + side * ((point - a).dot(side) / length).clamp(0.0, 1.0)
+}
+
+fn cross(a: Vector2, b: Vector2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector2, b: Vector2) -> bool {
+        a.distance_to(b) < 1e-3
+    }
+
+    fn square(center: Vector2, half: f32) -> Shape {
+        Shape::Polygon(vec![
+            center + Vector2::new(-half, -half),
+            center + Vector2::new(half, -half),
+            center + Vector2::new(half, half),
+            center + Vector2::new(-half, half),
+        ])
+    }
+
+    #[test]
+    fn ray_against_circle() {
+        let circle = Shape::Circle(Vector2::new(100.0, 0.0), 10.0);
+        let right = Vector2::new(1.0, 0.0);
+
+        let (distance, normal) = circle.raycast(Vector2::zero(), right, 1000.0).unwrap();
+        assert!((distance - 90.0).abs() < 1e-3);
+        assert!(close(normal, Vector2::new(-1.0, 0.0)));
+
+        // Off to the side, short of it and facing away.
+        assert!(circle
+            .raycast(Vector2::new(0.0, 11.0), right, 1000.0)
+            .is_none());
+        assert!(circle.raycast(Vector2::zero(), right, 50.0).is_none());
+        assert!(circle.raycast(Vector2::zero(), -right, 1000.0).is_none());
+
+        let inside = circle.raycast(Vector2::new(95.0, 0.0), right, 1000.0);
+        assert_eq!(inside.map(|(distance, _)| distance), Some(0.0));
+    }
+
+    #[test]
+    fn ray_against_polygon() {
+        let square = square(Vector2::new(100.0, 50.0), 20.0);
+
+        let (distance, normal) = square
+            .raycast(Vector2::new(0.0, 50.0), Vector2::new(1.0, 0.0), 1000.0)
+            .unwrap();
+        assert!((distance - 80.0).abs() < 1e-3);
+        assert!(close(normal, Vector2::new(-1.0, 0.0)));
+
+        let (distance, normal) = square
+            .raycast(Vector2::new(110.0, -100.0), Vector2::new(0.0, 1.0), 1000.0)
+            .unwrap();
+        assert!((distance - 130.0).abs() < 1e-3);
+        assert!(close(normal, Vector2::new(0.0, -1.0)));
+
+        assert!(square
+            .raycast(Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), 1000.0)
+            .is_none());
+
+        // The normal comes from the slanted side, not the direction of the ray.
+        let triangle = Shape::Polygon(vec![
+            Vector2::zero(),
+            Vector2::new(100.0, 0.0),
+            Vector2::new(0.0, 100.0),
+        ]);
+        let (distance, normal) = triangle
+            .raycast(Vector2::new(100.0, 20.0), Vector2::new(-1.0, 0.0), 1000.0)
+            .unwrap();
+        assert!((distance - 20.0).abs() < 1e-3);
+        assert!(close(normal, Vector2::new(1.0, 1.0).normalized()));
+    }
+
+    #[test]
+    fn fast_bullet_hits_ship_it_passed() {
+        let ship = Shape::Circle(Vector2::new(500.0, 0.0), 20.0);
+        let bullet = |x: f32, y: f32| Shape::Circle(Vector2::new(x, y), 10.0);
+
+        // However far the bullet moves in a tick, it can't skip over the ship.
+        for reach in [5.0, 50.0, 500.0, 5000.0] {
+            let from = Vector2::new(500.0 - reach, 0.0);
+            let t = time_of_impact(from, &bullet(500.0 + reach, 0.0), ship.center(), &ship);
+            let expected = ((reach - 30.0) / (reach * 2.0)).max(0.0);
+            assert!(t.is_some_and(|t| (t - expected).abs() < 1e-4), "{:?}", t);
+
+            let wide = time_of_impact(
+                Vector2::new(500.0 - reach, 31.0),
+                &bullet(500.0 + reach, 31.0),
+                ship.center(),
+                &ship,
+            );
+            assert_eq!(wide, None);
+        }
+
+        // Both moving, crossing paths halfway through the tick.
+        let moved = Shape::Circle(Vector2::new(500.0, 1000.0), 20.0);
+        let t = time_of_impact(
+            Vector2::new(0.0, 0.0),
+            &bullet(1000.0, 0.0),
+            Vector2::new(500.0, -1000.0),
+            &moved,
+        );
+        assert!(t.is_some_and(|t| t > 0.48 && t < 0.5), "{:?}", t);
+
+        // The same paths a little apart in time miss.
+        let late = time_of_impact(
+            Vector2::new(0.0, 0.0),
+            &bullet(1000.0, 0.0),
+            Vector2::new(500.0, -500.0),
+            &Shape::Circle(Vector2::new(500.0, 1500.0), 20.0),
+        );
+        assert_eq!(late, None);
+    }
+
+    #[test]
+    fn flat_rect_is_empty() {
+        assert!(Shape::Rect(Rectangle::new(10.0, 10.0, 0.0, 50.0)).is_empty());
+        assert!(Shape::Rect(Rectangle::new(10.0, 10.0, 50.0, 0.0)).is_empty());
+        assert!(!Shape::Rect(Rectangle::new(10.0, 10.0, 50.0, 50.0)).is_empty());
+    }
+
+    #[test]
+    fn surface_point_from_circle_center() {
+        let circle = Shape::Circle(Vector2::new(100.0, 100.0), 10.0);
+        let (surface, normal) = circle.surface_point(Vector2::new(100.0, 100.0));
+        assert!((normal.length() - 1.0).abs() < 1e-3);
+        assert!(close(surface, Vector2::new(100.0, 100.0) + normal * 10.0));
+
+        let (surface, normal) = circle.surface_point(Vector2::new(103.0, 104.0));
+        assert!(close(normal, Vector2::new(0.6, 0.8)));
+        assert!(close(surface, Vector2::new(106.0, 108.0)));
+    }
+
+    #[test]
+    fn rect_overlaps_polygon() {
+        let rect = Shape::Rect(Rectangle::new(0.0, 0.0, 100.0, 50.0));
+        let overlaps = |polygon: &Shape| {
+            time_of_impact(rect.center(), &rect, polygon.center(), polygon).is_some()
+        };
+
+        assert!(overlaps(&square(Vector2::new(110.0, 25.0), 20.0)));
+        assert!(!overlaps(&square(Vector2::new(130.0, 25.0), 20.0)));
+        // Corners touching still counts.
+        assert!(overlaps(&square(Vector2::new(120.0, 70.0), 20.0)));
+
+        // A diamond whose bounding box covers the rect's corner, but whose slanted side
+        // passes it by, and the same diamond moved close enough to touch.
+        let diamond = |center: Vector2| {
+            Shape::Polygon(vec![
+                center + Vector2::new(0.0, -20.0),
+                center + Vector2::new(20.0, 0.0),
+                center + Vector2::new(0.0, 20.0),
+                center + Vector2::new(-20.0, 0.0),
+            ])
+        };
+        let apart = diamond(Vector2::new(115.0, 65.0));
+        assert!(apart.bounds().check_collision_recs(&rect.bounds()));
+        assert!(!overlaps(&apart));
+        assert!(overlaps(&diamond(Vector2::new(108.0, 58.0))));
+
+        // Entirely inside.
+        assert!(overlaps(&square(Vector2::new(50.0, 25.0), 5.0)));
+    }
+
+    #[test]
+    fn segment_against_circle() {
+        let segment = Shape::Segment(Vector2::zero(), Vector2::new(100.0, 0.0));
+        let overlaps = |circle: &Shape| {
+            time_of_impact(circle.center(), circle, segment.center(), &segment).is_some()
+        };
+
+        assert!(!segment.is_empty());
+        assert!(close(segment.center(), Vector2::new(50.0, 0.0)));
+        assert!(overlaps(&Shape::Circle(Vector2::new(50.0, 8.0), 10.0)));
+        assert!(!overlaps(&Shape::Circle(Vector2::new(50.0, 15.0), 10.0)));
+        // Past the ends, only the nearest end counts.
+        assert!(overlaps(&Shape::Circle(Vector2::new(104.0, 3.0), 5.0)));
+        assert!(!overlaps(&Shape::Circle(Vector2::new(104.0, 4.0), 5.0)));
+
+        // A circle crossing it in one tick.
+        let circle = Shape::Circle(Vector2::new(50.0, 50.0), 10.0);
+        let t = time_of_impact(
+            Vector2::new(50.0, -50.0),
+            &circle,
+            segment.center(),
+            &segment,
+        );
+        assert!(t.is_some_and(|t| (t - 0.4).abs() < 1e-3));
+
+        // Rays hit it from either side.
+        let (distance, normal) = segment
+            .raycast(Vector2::new(30.0, -100.0), Vector2::new(0.0, 1.0), 1000.0)
+            .unwrap();
+        assert!((distance - 100.0).abs() < 1e-3);
+        assert!(close(normal, Vector2::new(0.0, -1.0)));
+        let (distance, normal) = segment
+            .raycast(Vector2::new(30.0, 40.0), Vector2::new(0.0, -1.0), 1000.0)
+            .unwrap();
+        assert!((distance - 40.0).abs() < 1e-3);
+        assert!(close(normal, Vector2::new(0.0, 1.0)));
+        assert!(segment
+            .raycast(Vector2::new(130.0, -100.0), Vector2::new(0.0, 1.0), 1000.0)
+            .is_none());
+    }
+
+    #[test]
+    fn segment_against_polygon() {
+        let segment = Shape::Segment(Vector2::zero(), Vector2::new(100.0, 0.0));
+        let overlaps = |other: &Shape| {
+            time_of_impact(segment.center(), &segment, other.center(), other).is_some()
+        };
+
+        assert!(overlaps(&square(Vector2::new(50.0, 15.0), 20.0)));
+        assert!(!overlaps(&square(Vector2::new(50.0, 30.0), 20.0)));
+        assert!(!overlaps(&square(Vector2::new(140.0, 0.0), 20.0)));
+        // Entirely inside.
+        assert!(overlaps(&square(Vector2::new(50.0, 0.0), 100.0)));
+
+        // Two segments on the same line only overlap if they share part of it.
+        let along =
+            |from: f32, to: f32| Shape::Segment(Vector2::new(from, 0.0), Vector2::new(to, 0.0));
+        assert!(!overlaps(&along(120.0, 200.0)));
+        assert!(overlaps(&along(90.0, 200.0)));
+        assert!(overlaps(&Shape::Segment(
+            Vector2::new(50.0, -10.0),
+            Vector2::new(60.0, 10.0)
+        )));
+    }
+}
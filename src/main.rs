@@ -1,71 +1,107 @@
-use std::env;
+use std::{env, process};
 
-use bean_script::util::{make_ref, MutRc};
-use bullet::BulletPool;
-use collision::{CollisionFrame, CollisionLayer};
-use itertools::Itertools;
-use object::Object;
+use options::{Command, Options};
 use raylib::prelude::*;
-use rock::Rock;
-use ship::Ship;
+use replay::{Recorder, Replay};
+use viewer::Viewer;
+use world::World;
 
 mod assets;
 mod bullet;
 mod collision;
 mod object;
+mod options;
+mod replay;
 mod rock;
+mod rules;
 mod ship;
+mod tournament;
+mod viewer;
+mod world;
+
+/// Longest frame the window loop will try to catch up on, so a stall doesn't turn into a
+/// burst of hundreds of ticks.
+const MAX_FRAME_TIME: f32 = 0.25;
+
+/// Ticks of the live game kept around for rewinding, one minute's worth.
+const HISTORY_LENGTH: usize = 60 * 60;
 
 fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            println!("\x1b[31;1merror\x1b[0m: {}", msg);
+            process::exit(2);
+        }
+    };
+
+    match options.command {
+        Command::Tournament => {
+            if let Err(msg) = tournament::run(&options, pick_seed(&options)) {
+                println!("\x1b[31;1merror\x1b[0m: {}", msg);
+                process::exit(2);
+            }
+        }
+        Command::Replay => run_replay(&options),
+        Command::Match if options.headless => run_headless(&options, pick_seed(&options)),
+        Command::Match => run_window(&options, pick_seed(&options)),
+    }
+}
+
+fn pick_seed(options: &Options) -> u64 {
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!("seed {}", seed);
+    seed
+}
+
+fn make_world(options: &Options, seed: u64) -> World {
+    let mut world = World::new(&options.paths, seed, &options.rules);
+
+    if let Some(path) = &options.record {
+        match Recorder::create(path, seed, &options.paths) {
+            Ok(recorder) => world.record(recorder),
+            Err(error) => println!(
+                "\x1b[31;1merror\x1b[0m: Failed to create replay {}: {}",
+                path, error
+            ),
+        }
+    }
+
+    world
+}
+
+fn run_window(options: &Options, seed: u64) {
     set_trace_log(TraceLogLevel::LOG_ERROR);
 
     let (mut rl, thread) = raylib::init().size(1280, 960).title("Hello, World").build();
 
     let assets = assets::load(&mut rl, &thread);
 
-    let bullet_pool = make_ref(BulletPool::new(60));
-
-    let mut positions: Vec<(f32, f32)> = Vec::new();
-    let mut ships: Vec<Ship> = make_ships(&mut positions, &bullet_pool);
-    let mut rocks: Vec<Rock> = make_rocks(&mut positions);
-
-    rocks.append(
-        &mut ships
-            .iter()
-            .combinations(2)
-            .flat_map(|pair| {
-                let [s1, s2] = &pair[..] else {
-                    return Vec::new();
-                };
-                let pos = s1.get_pos() + (s2.get_pos() - s1.get_pos()) * 0.5;
-
-                if positions
-                    .iter()
-                    .any(|(x, y)| (pos.x - x).abs() + (pos.y - y).abs() < 150.0)
-                {
-                    Vec::new()
-                } else {
-                    vec![Rock::new(pos.x, pos.y)]
-                }
-            })
-            .collect(),
-    );
+    let mut world = make_world(options, seed);
+    world.keep_history(HISTORY_LENGTH);
+    let names = world.names();
 
+    let mut viewer = Viewer::new();
     let mut ship_did_win = false;
+    let mut accumulator = 0.0;
 
     while !rl.window_should_close() {
         // UPDATE //
-        let collision_frame = CollisionFrame::new(vec![
-            ("ship", CollisionLayer::from(&ships)),
-            ("bullet", bullet_pool.borrow_mut().collision_layer()),
-            ("rock", CollisionLayer::from(&rocks)),
-        ]);
-
-        ships.update(&rl, &collision_frame);
-        rocks.update(&rl, &collision_frame);
-        bullet_pool.borrow_mut().update(&rl, &collision_frame);
+        let overflow = viewer.update(&rl, world.history().len());
+        if viewer.is_at_end(world.history().len()) {
+            if !viewer.paused {
+                accumulator += overflow.min(MAX_FRAME_TIME);
+                while accumulator >= World::TICK {
+                    world.step();
+                    accumulator -= World::TICK;
+                }
+            } else if viewer.wants_step() {
+                world.step();
+            }
+            viewer.seek_end(world.history().len());
+        }
 
-        if ships.iter().filter(|s| !s.is_destroyed()).count() == 1 && !ship_did_win {
+        if world.winner().is_some() && !ship_did_win {
             ship_did_win = true;
         }
 
@@ -73,19 +109,24 @@ fn main() {
         let mut d = rl.begin_drawing(&thread);
 
         d.clear_background(Color::BLACK);
-        rocks.draw(&mut d, &assets);
-        ships.draw(&mut d, &assets);
-        bullet_pool.borrow().draw(&mut d, &assets);
+        let history = world.history();
+        if viewer.is_at_end(history.len()) {
+            world.draw(&mut d, &assets, accumulator / World::TICK);
+        } else {
+            history[viewer.frame()].draw(&mut d, &assets, &names);
+        }
+        if !viewer.is_live(history.len()) {
+            viewer.draw(&mut d, history.len(), history[viewer.frame()].tick);
+        }
 
         if ship_did_win {
             d.draw_text(
-                &(ships
-                    .iter()
-                    .filter(|s| !s.is_destroyed())
+                &(world
+                    .alive()
                     .next()
                     .map(|s| s.name.clone())
                     .unwrap_or(String::new())
-                    + " won."),
+                    + &format!(" won. (seed {})", world.seed())),
                 10,
                 936,
                 24,
@@ -93,51 +134,67 @@ fn main() {
             );
         }
     }
+
+    world.stop_recording();
 }
 
-fn make_rocks(positions: &mut Vec<(f32, f32)>) -> Vec<Rock> {
-    (0..get_random_value(13, 16))
-        .map(|_| {
-            let mut pos = (
-                get_random_value::<i64>(80, 1200) as f32,
-                get_random_value::<i64>(80, 880) as f32,
-            );
-            while positions
-                .iter()
-                .any(|(x, y)| (pos.0 - x).abs() + (pos.1 - y).abs() < 200.0)
-            {
-                pos = (
-                    get_random_value::<i64>(80, 1200) as f32,
-                    get_random_value::<i64>(80, 880) as f32,
-                );
-            }
+fn run_replay(options: &Options) {
+    let replay = match Replay::load(&options.paths[0]) {
+        Ok(replay) => replay,
+        Err(msg) => {
+            println!("\x1b[31;1merror\x1b[0m: {}", msg);
+            process::exit(2);
+        }
+    };
+    println!("seed {}", replay.seed);
+
+    set_trace_log(TraceLogLevel::LOG_ERROR);
+
+    let (mut rl, thread) = raylib::init().size(1280, 960).title("Hello, World").build();
+
+    let assets = assets::load(&mut rl, &thread);
+    let names = replay.names();
+
+    let mut viewer = Viewer::new();
+
+    while !rl.window_should_close() {
+        // UPDATE //
+        viewer.update(&rl, replay.snapshots.len());
+
+        // DRAW //
+        let mut d = rl.begin_drawing(&thread);
 
-            positions.push(pos);
-            Rock::new(pos.0, pos.1)
-        })
-        .collect()
+        d.clear_background(Color::BLACK);
+        let snapshot = &replay.snapshots[viewer.frame()];
+        snapshot.draw(&mut d, &assets, &names);
+        viewer.draw(&mut d, replay.snapshots.len(), snapshot.tick);
+    }
 }
 
-fn make_ships(positions: &mut Vec<(f32, f32)>, bullet_pool: &MutRc<BulletPool>) -> Vec<Ship> {
-    env::args()
-        .skip(1)
-        .map(|p| {
-            let mut pos = (
-                get_random_value::<i64>(80, 1200) as f32,
-                get_random_value::<i64>(80, 880) as f32,
-            );
-            while positions
-                .iter()
-                .any(|(x, y)| (pos.0 - x).abs() + (pos.1 - y).abs() < 200.0)
-            {
-                pos = (
-                    get_random_value::<i64>(80, 1200) as f32,
-                    get_random_value::<i64>(80, 880) as f32,
-                );
-            }
+fn run_headless(options: &Options, seed: u64) {
+    let mut world = make_world(options, seed);
+
+    world.play_out(options.time_limit);
+    world.stop_recording();
+
+    for incident in world.incidents() {
+        println!("{}", incident);
+    }
 
-            positions.push(pos);
-            Ship::new(p, bullet_pool.clone(), pos.0, pos.1)
-        })
-        .collect()
+    let code = if let Some(winner) = world.winner() {
+        println!(
+            "{} won after {:.2}s. (seed {})",
+            winner.name,
+            world.time(),
+            world.seed()
+        );
+        0
+    } else {
+        println!("Draw after {:.2}s. (seed {})", world.time(), world.seed());
+        1
+    };
+    println!("exit code {}", code);
+    // Exiting skips destructors, so stop the scripts first.
+    drop(world);
+    process::exit(code);
 }
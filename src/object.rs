@@ -1,60 +1,77 @@
 use std::collections::VecDeque;
 
-use raylib::{collision, drawing::RaylibDrawHandle, math::Vector2, RaylibHandle};
+use raylib::{drawing::RaylibDrawHandle, math::Vector2};
 
 use crate::{
     assets::Assets,
-    collision::{Circle, CollisionFrame},
+    collision::{self, CollisionFrame, Shape},
 };
 
 pub trait Object {
-    fn update(&mut self, rl: &RaylibHandle, collision_frame: &CollisionFrame);
-    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets);
-    fn get_shape(&self) -> Circle;
+    fn update(&mut self, dt: f32, collision_frame: &CollisionFrame);
+    /// `alpha` is how far rendering is between the previous tick and the current one.
+    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, alpha: f32);
+    fn get_shape(&self) -> Shape;
+
+    /// Where the shape was at the start of the last tick, for objects that move.
+    fn get_prev_pos(&self) -> Vector2 {
+        self.get_shape().center()
+    }
+
+    /// Id of the ship responsible for this object, if any.
+    fn get_owner(&self) -> Option<usize> {
+        None
+    }
 
     fn is_colliding(&self, other: &dyn Object) -> bool {
         let shape1 = self.get_shape();
         let shape2 = other.get_shape();
-        collision::check_collision_circles(shape1.0, shape1.1, shape2.0, shape2.1)
+        collision::time_of_impact(shape1.center(), &shape1, shape2.center(), &shape2).is_some()
     }
 }
 
 impl<T: Object> Object for Vec<T> {
-    fn update(&mut self, rl: &RaylibHandle, collision_frame: &CollisionFrame) {
+    fn update(&mut self, dt: f32, collision_frame: &CollisionFrame) {
         for obj in self {
-            obj.update(rl, collision_frame);
+            obj.update(dt, collision_frame);
         }
     }
-    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets) {
+    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, alpha: f32) {
         for obj in self {
-            obj.draw(d, assets);
+            obj.draw(d, assets, alpha);
         }
     }
     fn is_colliding(&self, other: &dyn Object) -> bool {
         self.iter().any(|obj| obj.is_colliding(other))
     }
 
-    fn get_shape(&self) -> Circle {
-        (Vector2::zero(), 0.0)
+    fn get_shape(&self) -> Shape {
+        Shape::empty()
     }
 }
 
 impl<T: Object> Object for VecDeque<T> {
-    fn update(&mut self, rl: &RaylibHandle, collision_frame: &CollisionFrame) {
+    fn update(&mut self, dt: f32, collision_frame: &CollisionFrame) {
         for obj in self {
-            obj.update(rl, collision_frame);
+            obj.update(dt, collision_frame);
         }
     }
-    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets) {
+    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, alpha: f32) {
         for obj in self {
-            obj.draw(d, assets);
+            obj.draw(d, assets, alpha);
         }
     }
     fn is_colliding(&self, other: &dyn Object) -> bool {
         self.iter().any(|obj| obj.is_colliding(other))
     }
 
-    fn get_shape(&self) -> Circle {
-        (Vector2::zero(), 0.0)
+    fn get_shape(&self) -> Shape {
+        Shape::empty()
     }
 }
+
+/// Interpolates between two rotations in degrees along the shortest arc.
+pub fn lerp_rotation(from: f32, to: f32, amount: f32) -> f32 {
+    let delta = (to - from + 180.0).rem_euclid(360.0) - 180.0;
+    from + delta * amount
+}
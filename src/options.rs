@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use crate::rules::Rules;
+
+pub enum Command {
+    /// Play a single match between the given scripts.
+    Match,
+    /// Play every group of scripts in a directory against each other.
+    Tournament,
+    /// Watch a match recorded with `--record`.
+    Replay,
+}
+
+pub struct Options {
+    pub command: Command,
+    pub headless: bool,
+    /// Simulated seconds before a headless match is called a draw.
+    pub time_limit: f32,
+    /// Seed for arena generation and script randomness. Picked at random if not given.
+    pub seed: Option<u64>,
+    pub rules: Rules,
+    /// File to write a replay of the match to.
+    pub record: Option<String>,
+    /// Ships per tournament match.
+    pub group_size: usize,
+    /// How many times a tournament plays each group, with a different seed each time.
+    pub rounds: u32,
+    pub paths: Vec<String>,
+}
+
+impl Options {
+    const DEFAULT_TIME_LIMIT: f32 = 300.0;
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            command: Command::Match,
+            headless: false,
+            time_limit: Self::DEFAULT_TIME_LIMIT,
+            seed: None,
+            rules: Rules::default(),
+            record: None,
+            group_size: 2,
+            rounds: 1,
+            paths: Vec::new(),
+        };
+
+        let mut args = args.peekable();
+        match args.peek().map(String::as_str) {
+            Some("tournament") => options.command = Command::Tournament,
+            Some("replay") => options.command = Command::Replay,
+            _ => (),
+        }
+        if !matches!(options.command, Command::Match) {
+            args.next();
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless = true,
+                "--time-limit" => options.time_limit = parse_value(&arg, args.next())?,
+                "--seed" => options.seed = Some(parse_value(&arg, args.next())?),
+                "--lockstep" => {
+                    options.rules.lockstep =
+                        options.rules.lockstep.or(Some(Rules::DEFAULT_TICK_BUDGET))
+                }
+                "--record" => options.record = Some(parse_value(&arg, args.next())?),
+                "--no-friendly-fire" => options.rules.friendly_fire = false,
+                "--crash-eliminates" => options.rules.crash_eliminates = true,
+                "--sandbox" => options.rules.sandbox = true,
+                "--library" => {
+                    options.rules.sandbox = true;
+                    options.rules.library = Some(parse_value(&arg, args.next())?);
+                }
+                "--step-budget" => options.rules.step_budget = parse_value(&arg, args.next())?,
+                "--clip" => options.rules.clip_size = parse_value(&arg, args.next())?,
+                "--reload" => options.rules.reload_time = parse_value(&arg, args.next())?,
+                "--hp" => options.rules.ship_hp = parse_value(&arg, args.next())?,
+                "--group" => options.group_size = parse_value(&arg, args.next())?,
+                "--rounds" => options.rounds = parse_value(&arg, args.next())?,
+                "--tick-budget" => {
+                    let ms = parse_value(&arg, args.next())?;
+                    options.rules.lockstep = Some(Duration::from_millis(ms));
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}.", arg)),
+                _ => options.paths.push(arg),
+            }
+        }
+
+        match options.command {
+            Command::Match if options.paths.is_empty() => {
+                return Err(String::from("Expected at least one robot script."));
+            }
+            Command::Tournament if options.paths.len() != 1 => {
+                return Err(String::from("Expected a directory of robot scripts."));
+            }
+            Command::Replay if options.paths.len() != 1 => {
+                return Err(String::from("Expected a replay file."));
+            }
+            Command::Tournament if options.group_size < 2 => {
+                return Err(String::from("Tournament groups need at least two ships."));
+            }
+            _ => (),
+        }
+
+        // Without a window to pace it, the world would outrun wall-clock scripts and the
+        // outcome would depend on how fast the host is.
+        if options.headless || matches!(options.command, Command::Tournament) {
+            options.rules.lockstep = options.rules.lockstep.or(Some(Rules::DEFAULT_TICK_BUDGET));
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("Expected a value after {}.", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}.", flag, value))
+}
@@ -0,0 +1,300 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use raylib::{math::Vector2, prelude::RaylibDrawHandle};
+
+use crate::{
+    assets::Assets,
+    bullet::BulletSnapshot,
+    rock::RockSnapshot,
+    ship::{ShipSnapshot, State},
+};
+
+/// The state of every object in the world after one tick.
+pub struct Snapshot {
+    pub tick: u64,
+    pub ships: Vec<ShipSnapshot>,
+    pub bullets: Vec<BulletSnapshot>,
+    pub rocks: Vec<RockSnapshot>,
+}
+
+impl Snapshot {
+    fn new(tick: u64) -> Self {
+        Self {
+            tick,
+            ships: Vec::new(),
+            bullets: Vec::new(),
+            rocks: Vec::new(),
+        }
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, names: &[String]) {
+        for rock in &self.rocks {
+            rock.draw(d, assets);
+        }
+        for (ship, name) in self.ships.iter().zip(names) {
+            ship.draw(d, assets, name);
+        }
+        for bullet in &self.bullets {
+            bullet.draw(d, assets);
+        }
+    }
+}
+
+/// A match loaded back from a file written by [`Recorder`].
+pub struct Replay {
+    pub seed: u64,
+    pub bots: Vec<String>,
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let mut lines = file.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line) != Some(Recorder::HEADER) {
+            return Err(format!("{} is not a replay file.", path));
+        }
+
+        let mut replay = Self {
+            seed: 0,
+            bots: Vec::new(),
+            snapshots: Vec::new(),
+        };
+
+        for (i, line) in lines {
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let error = || format!("{}:{}: Invalid {} line.", path, i + 1, kind);
+            let mut fields = rest.splitn(7, ' ');
+
+            match kind {
+                "seed" => replay.seed = field(&mut fields).ok_or_else(error)?,
+                "bot" => {
+                    let (_hash, bot) = rest.split_once(' ').ok_or_else(error)?;
+                    replay.bots.push(String::from(bot));
+                }
+                "tick" => replay
+                    .snapshots
+                    .push(Snapshot::new(field(&mut fields).ok_or_else(error)?)),
+                "ship" | "bullet" | "rock" => {
+                    let snapshot = replay.snapshots.last_mut().ok_or_else(error)?;
+                    let pos = field(&mut fields)
+                        .zip(field(&mut fields))
+                        .map(|(x, y)| Vector2::new(x, y))
+                        .ok_or_else(error)?;
+
+                    match kind {
+                        "ship" => snapshot.ships.push(ShipSnapshot {
+                            pos,
+                            rotation: field(&mut fields).ok_or_else(error)?,
+                            turret: field(&mut fields).ok_or_else(error)?,
+                            hp: field(&mut fields).ok_or_else(error)?,
+                            max_hp: field(&mut fields).ok_or_else(error)?,
+                            state: field::<State>(&mut fields).ok_or_else(error)?,
+                        }),
+                        "bullet" => snapshot.bullets.push(BulletSnapshot {
+                            pos,
+                            rotation: field(&mut fields).ok_or_else(error)?,
+                            owner: field(&mut fields).ok_or_else(error)?,
+                        }),
+                        _ => snapshot.rocks.push(RockSnapshot {
+                            pos,
+                            hp: field(&mut fields).ok_or_else(error)?,
+                        }),
+                    }
+                }
+                "" => (),
+                _ => return Err(error()),
+            }
+        }
+
+        if replay.snapshots.is_empty() {
+            return Err(format!("{} has no recorded ticks.", path));
+        }
+
+        Ok(replay)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.bots
+            .iter()
+            .map(|bot| {
+                Path::new(bot)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or(bot.clone())
+            })
+            .collect()
+    }
+}
+
+fn field<'a, T: FromStr>(fields: &mut impl Iterator<Item = &'a str>) -> Option<T> {
+    fields.next()?.parse().ok()
+}
+
+/// Writes a match to a plain text replay file, one line per object per tick:
+///
+/// ```text
+/// virtual-space-robots replay 2
+/// seed 1234
+/// bot <fnv-1a hash of the script> <path>
+/// tick 0
+/// ship <x> <y> <rotation> <turret> <hp> <max hp> <state>
+/// bullet <x> <y> <rotation> <owner>
+/// rock <x> <y> <hp>
+/// ```
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    const HEADER: &'static str = "virtual-space-robots replay 2";
+
+    pub fn create(path: &str, seed: u64, bots: &[String]) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        writeln!(out, "{}", Self::HEADER)?;
+        writeln!(out, "seed {}", seed)?;
+        for bot in bots {
+            let source = fs::read(bot)?;
+            writeln!(out, "bot {:016x} {}", hash(&source), bot)?;
+        }
+
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        writeln!(self.out, "tick {}", snapshot.tick)?;
+        for ship in &snapshot.ships {
+            writeln!(
+                self.out,
+                "ship {} {} {} {} {} {} {}",
+                ship.pos.x,
+                ship.pos.y,
+                ship.rotation,
+                ship.turret,
+                ship.hp,
+                ship.max_hp,
+                ship.state
+            )?;
+        }
+        for bullet in &snapshot.bullets {
+            writeln!(
+                self.out,
+                "bullet {} {} {} {}",
+                bullet.pos.x, bullet.pos.y, bullet.rotation, bullet.owner
+            )?;
+        }
+        for rock in &snapshot.rocks {
+            writeln!(self.out, "rock {} {} {}", rock.pos.x, rock.pos.y, rock.hp)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// 64-bit FNV-1a. Stable across platforms and Rust versions, unlike `DefaultHasher`.
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("replay-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bot = dir.join("bot.bean");
+        fs::write(&bot, "a").unwrap();
+        let bot = bot.to_string_lossy().into_owned();
+        let path = dir.join("match.replay");
+        let path = path.to_str().unwrap();
+
+        let mut snapshot = Snapshot::new(1);
+        snapshot.ships.push(ShipSnapshot {
+            pos: Vector2::new(12.5, 300.0),
+            rotation: 90.0,
+            turret: -45.5,
+            hp: 2.5,
+            max_hp: 3.0,
+            state: State::Moving(40.0),
+        });
+        snapshot.ships.push(ShipSnapshot {
+            pos: Vector2::new(1000.0, 20.0),
+            rotation: 0.0,
+            turret: 0.0,
+            hp: 3.0,
+            max_hp: 3.0,
+            state: State::Crashed(String::from("Unknown value or function robot.")),
+        });
+        snapshot.bullets.push(BulletSnapshot {
+            pos: Vector2::new(1.0, 2.0),
+            rotation: 180.0,
+            owner: 1,
+        });
+        snapshot.rocks.push(RockSnapshot {
+            pos: Vector2::new(640.0, 480.0),
+            hp: 2,
+        });
+
+        let mut recorder = Recorder::create(path, 1234, std::slice::from_ref(&bot)).unwrap();
+        recorder.record(&Snapshot::new(0)).unwrap();
+        recorder.record(&snapshot).unwrap();
+        recorder.finish().unwrap();
+
+        let text = fs::read_to_string(path).unwrap();
+        let replay = Replay::load(path);
+        fs::remove_dir_all(&dir).unwrap();
+        let replay = replay.unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], Recorder::HEADER);
+        assert_eq!(lines[2], format!("bot af63dc4c8601ec8c {}", bot));
+
+        assert_eq!(replay.seed, 1234);
+        assert_eq!(replay.bots, vec![bot]);
+        assert_eq!(replay.snapshots.len(), 2);
+        assert!(replay.snapshots[0].ships.is_empty());
+
+        let loaded = &replay.snapshots[1];
+        assert_eq!(loaded.tick, 1);
+        assert_eq!(loaded.ships.len(), 2);
+        for (loaded, ship) in loaded.ships.iter().zip(&snapshot.ships) {
+            assert_eq!(loaded.pos, ship.pos);
+            assert_eq!(loaded.rotation, ship.rotation);
+            assert_eq!(loaded.turret, ship.turret);
+            assert_eq!(loaded.hp, ship.hp);
+            assert_eq!(loaded.max_hp, ship.max_hp);
+            assert_eq!(loaded.state.to_string(), ship.state.to_string());
+        }
+        assert_eq!(loaded.bullets.len(), 1);
+        assert_eq!(loaded.bullets[0].pos, Vector2::new(1.0, 2.0));
+        assert_eq!(loaded.bullets[0].rotation, 180.0);
+        assert_eq!(loaded.bullets[0].owner, 1);
+        assert_eq!(loaded.rocks.len(), 1);
+        assert_eq!(loaded.rocks[0].pos, Vector2::new(640.0, 480.0));
+        assert_eq!(loaded.rocks[0].hp, 2);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = std::env::temp_dir().join(format!("not-a-replay-{}", std::process::id()));
+        fs::write(&path, "seed 1\ntick 0\n").unwrap();
+        let replay = Replay::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert!(replay.is_err());
+    }
+}
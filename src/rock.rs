@@ -7,31 +7,50 @@ use raylib::{
 use crate::object::Object;
 
 pub struct Rock {
+    id: usize,
     pos: Vector2,
     hp: u8,
 }
 
 impl Rock {
-    pub fn new(x: f32, y: f32) -> Self {
+    /// Corners of the rock sprite's outline, relative to its center.
+    const OUTLINE: [(f32, f32); 11] = [
+        (-47.0, -16.0),
+        (-22.0, -43.0),
+        (21.0, -47.0),
+        (42.0, -30.0),
+        (47.0, -6.0),
+        (42.0, 34.0),
+        (26.0, 47.0),
+        (-26.0, 47.0),
+        (-34.0, 38.0),
+        (-43.0, 21.0),
+        (-47.0, -13.0),
+    ];
+
+    pub fn new(id: usize, x: f32, y: f32) -> Self {
         Self {
+            id,
             pos: Vector2::new(x, y),
             hp: 2,
         }
     }
-}
 
-impl Object for Rock {
-    fn update(
-        &mut self,
-        _rl: &raylib::prelude::RaylibHandle,
-        collision_frame: &crate::collision::CollisionFrame,
-    ) {
-        if collision_frame.check_collision(vec!["bullet"], self.get_shape()) && self.hp > 0 {
-            self.hp -= 1;
+    pub fn snapshot(&self) -> RockSnapshot {
+        RockSnapshot {
+            pos: self.pos,
+            hp: self.hp,
         }
     }
+}
+
+pub struct RockSnapshot {
+    pub pos: Vector2,
+    pub hp: u8,
+}
 
-    fn draw(&self, d: &mut raylib::prelude::RaylibDrawHandle, assets: &crate::assets::Assets) {
+impl RockSnapshot {
+    pub fn draw(&self, d: &mut raylib::prelude::RaylibDrawHandle, assets: &crate::assets::Assets) {
         if self.hp > 0 {
             d.draw_texture_pro(
                 &assets.rock,
@@ -43,12 +62,36 @@ impl Object for Rock {
             );
         }
     }
+}
+
+impl Object for Rock {
+    fn update(&mut self, _dt: f32, collision_frame: &crate::collision::CollisionFrame) {
+        for (layer, _) in collision_frame.contacts("rock", self.id) {
+            if layer == "bullet" {
+                self.hp = self.hp.saturating_sub(1);
+            }
+        }
+    }
+
+    fn draw(
+        &self,
+        d: &mut raylib::prelude::RaylibDrawHandle,
+        assets: &crate::assets::Assets,
+        _alpha: f32,
+    ) {
+        self.snapshot().draw(d, assets)
+    }
 
-    fn get_shape(&self) -> crate::collision::Circle {
+    fn get_shape(&self) -> crate::collision::Shape {
         if self.hp > 0 {
-            (self.pos, 45.0)
+            crate::collision::Shape::Polygon(
+                Self::OUTLINE
+                    .iter()
+                    .map(|&(x, y)| self.pos + Vector2::new(x, y))
+                    .collect(),
+            )
         } else {
-            (Vector2::zero(), 0.0)
+            crate::collision::Shape::empty()
         }
     }
 }
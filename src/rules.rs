@@ -0,0 +1,48 @@
+use std::{path::PathBuf, time::Duration};
+
+/// Settings that change how a match plays out, shared by every ship in the world.
+#[derive(Clone)]
+pub struct Rules {
+    /// When set, the world waits up to this long for each idle ship's script to issue its
+    /// next request before stepping, so script speed can't decide a match.
+    pub lockstep: Option<Duration>,
+    /// Health ships start with. Bullets take 1 and rocks take 0.5.
+    pub ship_hp: f32,
+    /// Whether ships can be damaged by their own bullets.
+    pub friendly_fire: bool,
+    /// Shots a ship can fire before it has to reload.
+    pub clip_size: u32,
+    /// Seconds it takes to refill an empty clip.
+    pub reload_time: f32,
+    /// Whether a ship whose script crashes is out of the match, rather than left drifting
+    /// in the arena as a target.
+    pub crash_eliminates: bool,
+    /// Builtin calls and loop iterations a script can run without waiting on its ship before
+    /// it's held back until the next tick.
+    pub step_budget: u32,
+    /// Whether scripts are restricted to whitelisted modules and simulated time.
+    pub sandbox: bool,
+    /// Directory sandboxed scripts can import local modules from. Without one, sandboxed
+    /// scripts can't import local modules at all.
+    pub library: Option<PathBuf>,
+}
+
+impl Rules {
+    pub const DEFAULT_TICK_BUDGET: Duration = Duration::from_millis(50);
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            lockstep: None,
+            ship_hp: 3.0,
+            friendly_fire: true,
+            clip_size: 5,
+            reload_time: 3.0,
+            crash_eliminates: false,
+            step_budget: 1_000,
+            sandbox: false,
+            library: None,
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use std::{
-    fmt::Debug,
-    fs,
+    cell::{Cell, RefCell},
+    fmt::{self, Debug, Display},
+    fs, mem,
     path::PathBuf,
     rc::Rc,
+    str::FromStr,
     sync::{
-        mpsc::{self, Receiver},
+        mpsc::{self, Receiver, RecvTimeoutError},
         Arc, Mutex,
     },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use bean_script::{
@@ -22,24 +25,34 @@ use raylib::{
     color::Color,
     drawing::{RaylibDraw, RaylibDrawHandle},
     math::{Rectangle, Vector2},
-    text, RaylibHandle,
+    text,
 };
 
 use self::api::APIRequest;
+pub use self::api::Event;
 use crate::{
     assets::Assets,
     bullet::BulletPool,
-    collision::{Circle, CollisionFrame},
-    object::Object,
+    collision::{self, CollisionFrame, Hit, RayHit, Shape},
+    object::{self, Object},
+    rules::Rules,
 };
 
 mod api;
-enum State {
+mod runtime;
+
+#[derive(Clone)]
+pub enum State {
     Destroyed,
     Waiting,
     Moving(f32),
     Turning(f32),
+    TurretTurning(f32),
     Shooting(f32),
+    Scanning(f32),
+    Sleeping(f32),
+    /// The script stopped with an error, summarised in the message.
+    Crashed(String),
 }
 
 impl State {
@@ -47,56 +60,308 @@ impl State {
         match req {
             APIRequest::Move(dist) => State::Moving(dist),
             APIRequest::Turn(dist) => State::Turning(dist),
+            APIRequest::TurretTurn(dist) => State::TurretTurning(dist),
             APIRequest::Shoot => State::Shooting(Ship::SHOOT_COOLDOWN),
+            APIRequest::Scan(fov, range) => State::Scanning(Ship::scan_time(fov, range)),
+            APIRequest::Sleep(time) => State::Sleeping(time),
+            APIRequest::Crash(error) => State::Crashed(error),
+            // Holding the script for a single tick.
+            APIRequest::Throttle => State::Sleeping(0.0),
+            // Answered on the tick they arrive.
+            APIRequest::RaycastAt(..)
+            | APIRequest::Finished
+            | APIRequest::MoveAsync(_)
+            | APIRequest::TurnAsync(_)
+            | APIRequest::Stop => State::Waiting,
+        }
+    }
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Destroyed => write!(f, "destroyed"),
+            State::Waiting => write!(f, "waiting"),
+            State::Moving(dist) => write!(f, "moving {}", dist),
+            State::Turning(dist) => write!(f, "turning {}", dist),
+            State::TurretTurning(dist) => write!(f, "turret_turning {}", dist),
+            State::Shooting(cooldown) => write!(f, "shooting {}", cooldown),
+            State::Scanning(cooldown) => write!(f, "scanning {}", cooldown),
+            State::Sleeping(time) => write!(f, "sleeping {}", time),
+            State::Crashed(error) => write!(f, "crashed {}", error),
+        }
+    }
+}
+
+impl FromStr for State {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let kind = words.next().unwrap_or("");
+        let mut value = || -> Result<f32, String> {
+            words
+                .next()
+                .and_then(|word| word.parse().ok())
+                .ok_or(format!("Expected a number after {}.", kind))
+        };
+
+        match kind {
+            "destroyed" => Ok(State::Destroyed),
+            "waiting" => Ok(State::Waiting),
+            "moving" => Ok(State::Moving(value()?)),
+            "turning" => Ok(State::Turning(value()?)),
+            "turret_turning" => Ok(State::TurretTurning(value()?)),
+            "shooting" => Ok(State::Shooting(value()?)),
+            "scanning" => Ok(State::Scanning(value()?)),
+            "sleeping" => Ok(State::Sleeping(value()?)),
+            "crashed" => Ok(State::Crashed(String::from(
+                s.trim_start()
+                    .split_once(' ')
+                    .map_or("", |(_, error)| error),
+            ))),
+            _ => Err(format!("Unknown ship state {}.", s)),
+        }
+    }
+}
+
+pub struct ShipSnapshot {
+    pub pos: Vector2,
+    pub rotation: f32,
+    /// Turret heading relative to the hull.
+    pub turret: f32,
+    pub hp: f32,
+    pub max_hp: f32,
+    pub state: State,
+}
+
+impl ShipSnapshot {
+    pub fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, name: &str) {
+        if matches!(self.state, State::Destroyed) || self.hp <= 0.0 {
+            d.draw_texture_pro(
+                &assets.ship_dead,
+                Rectangle::new(0.0, 0.0, 50.0, 50.0),
+                Rectangle::new(self.pos.x, self.pos.y, 50.0, 50.0),
+                Vector2::new(25.0, 25.0),
+                self.rotation + 90.0,
+                Color::WHITE,
+            );
+        } else {
+            d.draw_texture_pro(
+                &assets.ship,
+                Rectangle::new(0.0, 0.0, 50.0, 50.0),
+                Rectangle::new(self.pos.x, self.pos.y, 50.0, 50.0),
+                Vector2::new(25.0, 25.0),
+                self.rotation + 90.0,
+                Color::WHITE,
+            );
+            d.draw_texture_pro(
+                &assets.turret,
+                Rectangle::new(0.0, 0.0, 50.0, 50.0),
+                Rectangle::new(self.pos.x, self.pos.y, 50.0, 50.0),
+                Vector2::new(25.0, 25.0),
+                self.rotation + self.turret + 90.0,
+                Color::WHITE,
+            );
+
+            let health = (self.hp / self.max_hp).clamp(0.0, 1.0);
+            d.draw_rectangle(
+                self.pos.x as i32 - 20,
+                self.pos.y as i32 - 30,
+                40,
+                4,
+                Color::DARKGRAY,
+            );
+            d.draw_rectangle(
+                self.pos.x as i32 - 20,
+                self.pos.y as i32 - 30,
+                (40.0 * health) as i32,
+                4,
+                Color::GREEN,
+            );
+        }
+        d.draw_text(
+            name,
+            self.pos.x as i32 - text::measure_text(name, 18) / 2,
+            self.pos.y as i32 - 50,
+            18,
+            Color::GREEN,
+        );
+        if let State::Crashed(error) = &self.state {
+            d.draw_text(
+                error,
+                self.pos.x as i32 - text::measure_text(error, 14) / 2,
+                self.pos.y as i32 - 68,
+                14,
+                Color::RED,
+            );
         }
     }
 }
 
+/// Something picked up by a radar scan.
+#[derive(Clone)]
+pub struct Contact {
+    kind: &'static str,
+    /// Distance to the object's edge in pixels.
+    distance: f32,
+    /// Degrees clockwise from the ship's heading, between -180 and 180.
+    bearing: f32,
+}
+
 pub struct ShipHandle {
     raycast: String,
     raycast_dist: f32,
+    /// Where the turret's ray ends.
+    rayhit: Vector2,
     pos: Vector2,
     rotation: f32,
+    /// Absolute turret heading, to compare with `rotation`.
+    turret_rotation: f32,
+    /// Whether the ship is still moving or turning.
+    busy: bool,
+    /// Whether the last move was cut short by the edge of the arena.
+    move_blocked: bool,
+    health: f32,
+    ammo: u32,
+    /// Whether the last `shoot` request actually fired a bullet.
+    shot_fired: bool,
+    /// Set when the ship is destroyed or the match ends, so the script stops at its next
+    /// API call.
+    stopped: bool,
+    /// Results of the last scan, nearest first.
+    contacts: Vec<Contact>,
+    /// What the last `raycast_at` hit.
+    ray_at: RayHit,
+    /// Events the script hasn't handled yet, oldest first.
+    events: Vec<Event>,
 }
 
 pub struct Ship {
     pub name: String,
+    id: usize,
     pos: Vector2,
     rotation: f32,
+    prev_pos: Vector2,
+    prev_rotation: f32,
+    /// Turret heading relative to the hull, so the turret turns along with it.
+    turret: f32,
+    prev_turret: f32,
+    /// Distance left to move, negative when reversing.
+    move_left: f32,
+    /// Degrees left to turn, negative when turning anticlockwise.
+    turn_left: f32,
+    move_blocked: bool,
     thread: JoinHandle<()>,
     rx: Receiver<APIRequest>,
     handle: Arc<Mutex<ShipHandle>>,
     state: State,
     bullet_pool: MutRc<BulletPool>,
+    rules: Rules,
+    kills: u32,
+    killed_by: Option<usize>,
+    hp: f32,
+    /// Seconds left before the ship can take damage again.
+    invulnerable: f32,
+    ammo: u32,
+    /// Seconds left until the clip is refilled, or zero if the ship isn't reloading.
+    reload: f32,
+    shot_fired: bool,
+    /// Summary of the error the script crashed with, if it did.
+    error: Option<String>,
+    /// Times the script was held back for running past its step budget.
+    throttled: u32,
+    /// Lockstep ticks in a row the script didn't answer within the tick budget.
+    missed_ticks: u32,
+    /// Whether the script ran to the end, so there's nothing left to wait on.
+    finished: bool,
+    contacts: Vec<Contact>,
+    ray_at: RayHit,
+    /// Events since the handle was last refreshed.
+    events: Vec<Event>,
+    bullet_near: bool,
+    touching_wall: bool,
+    /// Whether the script gets control back once the tick's contacts are resolved.
+    should_unpark: bool,
 }
 
 impl Ship {
+    const RADIUS: f32 = 20.0;
     const MOVE_SPEED: f32 = 150.0;
     const TURN_SPEED: f32 = 360.0;
+    const TURRET_TURN_SPEED: f32 = 540.0;
     const SHOOT_OFFSET: f32 = 40.1;
+    /// What rays cast by ships can hit.
+    const RAY_LAYERS: [&'static str; 3] = ["ship", "rock", "bullet"];
     const SHOOT_COOLDOWN: f32 = 1.0;
+    const BULLET_DAMAGE: f32 = 1.0;
+    const ROCK_DAMAGE: f32 = 0.5;
+    const INVULNERABILITY: f32 = 0.5;
+    const ROCK_PUSHBACK: f32 = 10.0;
+    const MAX_SCAN_RANGE: f32 = 1000.0;
+    /// Seconds a full circle scan at the maximum range takes. Smaller scans are quicker in
+    /// proportion to the area they cover.
+    const SCAN_TIME: f32 = 1.0;
+    /// How close someone else's bullet has to come to trigger `bullet_near`.
+    const BULLET_NEAR_RANGE: f32 = 100.0;
+    /// Events kept for a script that isn't handling them, so a busy script can't use up
+    /// memory.
+    const MAX_EVENTS: usize = 64;
+    /// Lockstep ticks in a row a script can miss the tick budget before it's crashed. The
+    /// step budget should hold any script back well before this.
+    const MAX_MISSED_TICKS: u32 = 20;
 
-    pub fn new(path: String, bullet_pool: MutRc<BulletPool>, x: f32, y: f32) -> Self {
+    pub fn new(
+        id: usize,
+        path: String,
+        bullet_pool: MutRc<BulletPool>,
+        rules: Rules,
+        x: f32,
+        y: f32,
+        seed: u64,
+    ) -> Self {
         let (tx, rx) = mpsc::channel();
+        let exit_tx = tx.clone();
         let handle = Arc::new(Mutex::new(ShipHandle {
             raycast: String::from("none"),
             raycast_dist: -1.0,
+            rayhit: Vector2::new(x, y),
             pos: Vector2::new(x, y),
             rotation: 0.0,
+            turret_rotation: 0.0,
+            busy: false,
+            move_blocked: false,
+            health: rules.ship_hp,
+            ammo: rules.clip_size,
+            shot_fired: false,
+            stopped: false,
+            contacts: Vec::new(),
+            ray_at: RayHit::none(),
+            events: Vec::new(),
         }));
         let handle_read = Arc::clone(&handle);
+        let handle_stopped = Arc::clone(&handle);
         let name = PathBuf::from(path.clone())
             .file_stem()
             .unwrap()
             .to_str()
             .unwrap()
             .to_string();
+        let lockstep = rules.lockstep.is_some();
+        let step_budget = rules.step_budget;
+        let sandbox = rules.sandbox;
+        let library = rules.library.clone();
 
         let thread = thread::spawn(move || {
             let file = fs::read_to_string(path.clone()).expect("Failed to open file");
 
             let mut dir_path = PathBuf::from(path.clone());
             dir_path.pop();
+            if sandbox {
+                // Local imports resolve against the module's directory, so rooting it at the
+                // library keeps scripts from reaching anything next to them.
+                dir_path = library.clone().unwrap_or_default();
+            }
 
             let mut registry = ModuleRegistry::new(RegistryFeatures::default());
             registry
@@ -105,31 +370,92 @@ impl Ship {
             registry
                 .metadata
                 .insert(String::from("mutex"), Box::new(handle_read));
+            let steps = Rc::new(Cell::new(0u32));
+            registry
+                .metadata
+                .insert(String::from("steps"), Box::new(Rc::clone(&steps)));
+            registry.metadata.insert(
+                String::from("handlers"),
+                Box::new(Rc::new(RefCell::new(api::Handlers::default()))),
+            );
             registry.register_initialized_builtin(
                 String::from("robot_api"),
                 BuiltinModule::new(api::construct, registry.features),
             );
+            runtime::patch(&registry, |module, functions| {
+                runtime::seeded_rand(module, seed);
+                if sandbox {
+                    runtime::sandbox(module, functions, library.is_some());
+                } else if lockstep {
+                    // Wall-clock sleeps would hand the script real time the world isn't
+                    // waiting on, so count them in simulated time instead.
+                    module.function("sleep", api::fn_sleep);
+                }
+            });
+            // Wraps what the patches above left in place, so their builtins are counted too.
+            runtime::patch(&registry, |module, functions| {
+                runtime::counted(module, functions, move |scope| {
+                    api::count_step(scope, &steps, step_budget)
+                });
+            });
 
             let scope = make_ref(CustomModule::new(make_ref(registry), dir_path));
             let result = bean_script::interpret(file, Rc::clone(&scope));
 
-            if let Err(error) = result {
-                println!(
-                    "\x1b[31;1merror\x1b[0m: {}",
-                    error.trace(ErrorSource::File(path))
-                );
+            match result {
+                // Functions and handlers the script defined hold on to the registry, and with
+                // it `tx`, so the channel doesn't close on its own when the script ends.
+                Ok(_) => {
+                    let _ = exit_tx.send(APIRequest::Finished);
+                }
+                Err(_) if handle_stopped.lock().unwrap().stopped => {
+                    // The ship was told to stop, so unwinding is expected.
+                }
+                Err(error) => {
+                    let error = error.trace(ErrorSource::File(path)).to_string();
+                    println!("\x1b[31;1merror\x1b[0m: {}", error);
+                    // The first line is the message itself, the rest is the trace.
+                    let summary = error.lines().next().unwrap_or_default();
+                    let _ = exit_tx.send(APIRequest::Crash(String::from(summary)));
+                }
             }
         });
 
         Self {
             name,
+            id,
             pos: Vector2::new(x, y),
             rotation: 0.0,
+            prev_pos: Vector2::new(x, y),
+            prev_rotation: 0.0,
+            turret: 0.0,
+            prev_turret: 0.0,
+            move_left: 0.0,
+            turn_left: 0.0,
+            move_blocked: false,
             thread,
             rx,
             handle,
             state: State::Waiting,
             bullet_pool,
+            hp: rules.ship_hp,
+            ammo: rules.clip_size,
+            rules,
+            kills: 0,
+            killed_by: None,
+            invulnerable: 0.0,
+            reload: 0.0,
+            shot_fired: false,
+            error: None,
+            throttled: 0,
+            missed_ticks: 0,
+            finished: false,
+            contacts: Vec::new(),
+            ray_at: RayHit::none(),
+            events: Vec::new(),
+            bullet_near: false,
+            touching_wall: false,
+            should_unpark: false,
         }
     }
 
@@ -139,22 +465,240 @@ impl Ship {
     }
 
     fn make_handle(&self, collision_frame: &CollisionFrame) -> ShipHandle {
-        let (raycast, raycast_dist) = collision_frame.raycast(
-            vec!["ship", "rock", "bullet"],
-            self.pos
-                + Vector2::new(
-                    self.rotation.to_radians().cos(),
-                    self.rotation.to_radians().sin(),
-                ) * Self::SHOOT_OFFSET,
-            self.rotation,
-            20.0,
-        );
+        let aim = self.aim();
+        let origin = self.ray_origin(aim);
+        let (raycast, raycast_dist) =
+            collision_frame.raycast(Self::RAY_LAYERS.to_vec(), origin, aim);
 
         ShipHandle {
             raycast,
             raycast_dist,
+            rayhit: origin
+                + Vector2::new(aim.to_radians().cos(), aim.to_radians().sin()) * raycast_dist,
             pos: self.pos,
             rotation: self.rotation,
+            turret_rotation: self.aim(),
+            busy: self.move_left != 0.0 || self.turn_left != 0.0,
+            move_blocked: self.move_blocked,
+            health: self.hp,
+            ammo: self.ammo,
+            shot_fired: self.shot_fired,
+            stopped: false,
+            contacts: self.contacts.clone(),
+            ray_at: self.ray_at.clone(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Absolute heading of the turret, which bullets and the forward raycast follow.
+    fn aim(&self) -> f32 {
+        (self.rotation + self.turret).rem_euclid(360.0)
+    }
+
+    /// Where rays cast and bullets fired towards `rotation` start, just clear of the hull.
+    fn ray_origin(&self, rotation: f32) -> Vector2 {
+        self.pos
+            + Vector2::new(rotation.to_radians().cos(), rotation.to_radians().sin())
+                * Self::SHOOT_OFFSET
+    }
+
+    /// Casts a ray in the direction of `rotation`.
+    fn cast_ray(&self, rotation: f32, collision_frame: &CollisionFrame) -> RayHit {
+        collision_frame.cast(
+            Self::RAY_LAYERS.to_vec(),
+            self.ray_origin(rotation),
+            rotation,
+        )
+    }
+
+    /// Moves and turns the ship towards whatever's left of its current `move` and `turn`,
+    /// which may both be in progress at once.
+    fn advance_motion(&mut self, dt: f32) {
+        let turned = self
+            .turn_left
+            .clamp(-Self::TURN_SPEED * dt, Self::TURN_SPEED * dt);
+        self.rotation = (self.rotation + turned).rem_euclid(360.0);
+        self.turn_left -= turned;
+
+        let moved = self
+            .move_left
+            .clamp(-Self::MOVE_SPEED * dt, Self::MOVE_SPEED * dt);
+        self.pos += Vector2::new(
+            self.rotation.to_radians().cos(),
+            self.rotation.to_radians().sin(),
+        ) * moved;
+        self.move_left -= moved;
+    }
+
+    fn scan_time(fov: f32, range: f32) -> f32 {
+        Self::SCAN_TIME * (fov / 360.0) * (range / Self::MAX_SCAN_RANGE).powi(2)
+    }
+
+    fn scan(&self, fov: f32, range: f32, collision_frame: &CollisionFrame) -> Vec<Contact> {
+        let mut contacts: Vec<Contact> = collision_frame
+            .scan(
+                vec!["ship", "rock", "bullet"],
+                self.pos,
+                self.rotation,
+                fov,
+                range,
+                // A ship's owner is the ship itself.
+                |layer, hit| layer != "ship" || hit.owner != Some(self.id),
+            )
+            .into_iter()
+            .map(|(kind, hit, distance)| {
+                let offset = hit.shape.center() - self.pos;
+                Contact {
+                    kind,
+                    distance,
+                    bearing: (offset.y.atan2(offset.x).to_degrees() - self.rotation + 540.0)
+                        .rem_euclid(360.0)
+                        - 180.0,
+                }
+            })
+            .collect();
+        contacts.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        contacts
+    }
+
+    fn damage(&mut self, amount: f32, source: Option<usize>) {
+        if self.invulnerable > 0.0 || self.is_destroyed() {
+            return;
+        }
+
+        self.hp -= amount;
+        self.invulnerable = Self::INVULNERABILITY;
+        self.events.push(Event::Hit(amount));
+        if self.hp <= 0.0 {
+            self.state = State::Destroyed;
+            self.killed_by = source;
+            self.stop();
+        }
+    }
+
+    /// Queues an event for the script's handlers.
+    pub fn notify(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /// Checks for events that depend on the ship's surroundings. They fire once when something
+    /// starts happening, rather than on every tick it goes on.
+    fn sense(&mut self, collision_frame: &CollisionFrame) {
+        let nearest_bullet = collision_frame
+            .scan(
+                vec!["bullet"],
+                self.pos,
+                0.0,
+                360.0,
+                Self::BULLET_NEAR_RANGE,
+                |_, hit| hit.owner != Some(self.id),
+            )
+            .iter()
+            .map(|(_, _, distance)| *distance)
+            .min_by(f32::total_cmp);
+        if let Some(dist) = nearest_bullet.filter(|_| !self.bullet_near) {
+            self.events.push(Event::BulletNear(dist));
+        }
+        self.bullet_near = nearest_bullet.is_some();
+    }
+
+    /// Stops the ship at the edge of the arena if it ran into a wall, cutting short any move
+    /// that ran into it.
+    fn hit_walls(&mut self, touched: bool) {
+        let (pos, touching_wall) = if touched {
+            collision::keep_in_arena((self.pos, Self::RADIUS))
+        } else {
+            (self.pos, false)
+        };
+        self.pos = pos;
+
+        if touching_wall && self.move_left != 0.0 {
+            self.move_left = 0.0;
+            self.move_blocked = true;
+        }
+        if touching_wall && !self.touching_wall {
+            self.events.push(Event::CollideWall);
+        }
+        self.touching_wall = touching_wall;
+    }
+
+    /// Reacts to what the ship ran into while it moved this tick, then hands the script its
+    /// new surroundings. Runs after every ship's [`Object::update`], once their moves have
+    /// been swept for contacts.
+    pub fn resolve_contacts(&mut self, collision_frame: &CollisionFrame) {
+        if self.is_eliminated() {
+            return;
+        }
+
+        let contacts: Vec<(&str, Hit)> = collision_frame.contacts("ship", self.id).collect();
+        let mut touched_wall = false;
+        for (layer, hit) in contacts {
+            match layer {
+                "bullet" => self.damage(Self::BULLET_DAMAGE, hit.owner),
+                "rock" => {
+                    let (surface, away) = hit.shape.surface_point(self.pos);
+                    self.pos = surface + away * (Self::RADIUS + Self::ROCK_PUSHBACK);
+                    self.damage(Self::ROCK_DAMAGE, None);
+                    // Being pushed off can take it past a wall it hadn't reached yet.
+                    touched_wall = true;
+                }
+                "wall" => touched_wall = true,
+                _ => (),
+            }
+        }
+        self.hit_walls(touched_wall);
+
+        if !self.is_destroyed() {
+            self.sense(collision_frame);
+
+            let mut raycast_lock = self.handle.lock().unwrap();
+            let mut events = mem::take(&mut raycast_lock.events);
+            events.append(&mut self.events);
+            if events.len() > Self::MAX_EVENTS {
+                events.drain(..events.len() - Self::MAX_EVENTS);
+            }
+            *raycast_lock = self.make_handle(collision_frame);
+            raycast_lock.events = events;
+            drop(raycast_lock);
+
+            if self.should_unpark {
+                self.next();
+            }
+        }
+        self.should_unpark = false;
+    }
+
+    /// Stops the ship where it is after its script failed with `error`.
+    fn crash(&mut self, error: String) {
+        self.move_left = 0.0;
+        self.turn_left = 0.0;
+        if self.rules.crash_eliminates {
+            self.hp = 0.0;
+        }
+        self.state = State::Crashed(error.clone());
+        self.error = Some(error);
+    }
+
+    /// Tells the script to stop at its next API call and wakes it up if it's waiting on one.
+    pub fn stop(&self) {
+        self.handle.lock().unwrap().stopped = true;
+        self.thread.thread().unpark();
+    }
+
+    /// Waits until `deadline` for the script thread to finish. Scripts stuck in a loop
+    /// without API calls can't be stopped, so those threads are left behind.
+    pub fn join(self, deadline: Instant) {
+        while !self.thread.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        if self.thread.is_finished() {
+            let _ = self.thread.join();
+        } else {
+            println!(
+                "\x1b[33;1mwarning\x1b[0m: {}'s script didn't stop in time.",
+                self.name
+            );
         }
     }
 
@@ -166,119 +710,243 @@ impl Ship {
         }
     }
 
+    /// Whether the ship is out of the match, either destroyed or crashed under
+    /// [`Rules::crash_eliminates`].
+    pub fn is_eliminated(&self) -> bool {
+        self.is_destroyed() || (self.error.is_some() && self.rules.crash_eliminates)
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn throttled(&self) -> u32 {
+        self.throttled
+    }
+
     pub fn get_pos(&self) -> Vector2 {
         self.pos
     }
+
+    pub fn snapshot(&self) -> ShipSnapshot {
+        ShipSnapshot {
+            pos: self.pos,
+            rotation: self.rotation,
+            turret: self.turret,
+            hp: self.hp,
+            max_hp: self.rules.ship_hp,
+            state: self.state.clone(),
+        }
+    }
+
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn kills(&self) -> u32 {
+        self.kills
+    }
+
+    pub fn add_kill(&mut self) {
+        self.kills += 1;
+    }
+
+    /// The ship whose bullet destroyed this one, if it was shot down.
+    pub fn killed_by(&self) -> Option<usize> {
+        self.killed_by
+    }
 }
 
 impl Object for Ship {
-    fn update(&mut self, rl: &RaylibHandle, collision_frame: &CollisionFrame) {
+    fn update(&mut self, dt: f32, collision_frame: &CollisionFrame) {
+        self.prev_pos = self.pos;
+        self.prev_rotation = self.rotation;
+        self.prev_turret = self.turret;
+
         let mut should_unpark = false;
         match &self.state {
             State::Waiting => {
-                let received = self.rx.try_recv();
-                if let Ok(msg) = received {
-                    if let APIRequest::Shoot = &msg {
-                        self.bullet_pool
-                            .borrow_mut()
-                            .shoot(
-                                self.pos
-                                    + Vector2::new(
-                                        self.rotation.to_radians().cos(),
-                                        self.rotation.to_radians().sin(),
-                                    ) * Self::SHOOT_OFFSET,
-                                self.rotation,
-                            )
-                            .unwrap();
+                let received = match self.rules.lockstep {
+                    _ if self.finished => None,
+                    Some(budget) => match self.rx.recv_timeout(budget) {
+                        Ok(msg) => {
+                            self.missed_ticks = 0;
+                            Some(msg)
+                        }
+                        Err(RecvTimeoutError::Timeout) => {
+                            self.missed_ticks += 1;
+                            None
+                        }
+                        Err(RecvTimeoutError::Disconnected) => None,
+                    },
+                    None => self.rx.try_recv().ok(),
+                };
+                if self.missed_ticks >= Self::MAX_MISSED_TICKS {
+                    self.crash(format!(
+                        "Script missed the tick budget {} ticks in a row.",
+                        self.missed_ticks
+                    ));
+                    // It's busy rather than waiting, so it stops at its next API call or
+                    // step over budget.
+                    self.stop();
+                }
+                if let Some(mut msg) = received {
+                    match &mut msg {
+                        APIRequest::Shoot => {
+                            self.shot_fired = self.ammo > 0;
+                            if self.shot_fired {
+                                let aim = self.aim();
+                                self.bullet_pool.borrow_mut().shoot(
+                                    self.ray_origin(aim),
+                                    aim,
+                                    self.id,
+                                );
+                                self.ammo -= 1;
+                                if self.ammo == 0 {
+                                    self.reload = self.rules.reload_time;
+                                }
+                            } else {
+                                // Nothing to wait on, so hand control straight back to the
+                                // script.
+                                should_unpark = true;
+                            }
+                        }
+                        APIRequest::Scan(fov, range) => {
+                            self.contacts = self.scan(*fov, *range, collision_frame);
+                        }
+                        APIRequest::RaycastAt(angle, absolute) => {
+                            let rotation = if *absolute {
+                                *angle
+                            } else {
+                                self.rotation + *angle
+                            };
+                            self.ray_at = self.cast_ray(rotation, collision_frame);
+                            // The answer is ready right away, so the script doesn't have to
+                            // wait out a state.
+                            should_unpark = true;
+                        }
+                        // The tick the request arrives on counts towards it, like it does
+                        // for moves and turns.
+                        APIRequest::Sleep(time) if dt < *time => *time -= dt,
+                        APIRequest::Sleep(_) => should_unpark = true,
+                        APIRequest::Throttle => self.throttled += 1,
+                        APIRequest::Finished => self.finished = true,
+                        APIRequest::Move(dist) => {
+                            self.move_left = *dist;
+                            self.move_blocked = false;
+                        }
+                        APIRequest::Turn(dist) => self.turn_left = *dist,
+                        APIRequest::MoveAsync(dist) => {
+                            self.move_left = *dist;
+                            self.move_blocked = false;
+                            should_unpark = true;
+                        }
+                        APIRequest::TurnAsync(dist) => {
+                            self.turn_left = *dist;
+                            should_unpark = true;
+                        }
+                        APIRequest::Stop => {
+                            self.move_left = 0.0;
+                            self.turn_left = 0.0;
+                            should_unpark = true;
+                        }
+                        APIRequest::Crash(error) => self.crash(error.clone()),
+                        _ => (),
+                    }
+                    if !should_unpark {
+                        self.state = State::from_req(msg);
                     }
-                    self.state = State::from_req(msg);
                 }
             }
-            State::Moving(dist) => {
-                let dist_moved = dist.abs().min(Self::MOVE_SPEED * rl.get_frame_time());
-                self.pos += Vector2::new(
-                    self.rotation.to_radians().cos(),
-                    self.rotation.to_radians().sin(),
-                ) * dist_moved
-                    * dist.signum();
+            // The motion itself is advanced below, so it can carry on alongside other
+            // actions.
+            State::Moving(_) | State::Turning(_) => (),
+            State::TurretTurning(dist) => {
+                let dist_moved = dist.abs().min(Self::TURRET_TURN_SPEED * dt);
+                self.turret = (self.turret + dist_moved * dist.signum()).rem_euclid(360.0);
                 if dist_moved < dist.abs() {
-                    self.state = State::Moving(dist - dist_moved * dist.signum());
+                    self.state = State::TurretTurning(dist - dist_moved * dist.signum());
                 } else {
                     should_unpark = true;
                 }
             }
-            State::Turning(dist) => {
-                let dist_moved = dist.abs().min(Self::TURN_SPEED * rl.get_frame_time());
-                self.rotation = (self.rotation + dist_moved * dist.signum()).rem_euclid(360.0);
-                if dist_moved < dist.abs() {
-                    self.state = State::Turning(dist - dist_moved * dist.signum());
+            State::Shooting(cooldown) => {
+                if dt < *cooldown {
+                    self.state = State::Shooting(cooldown - dt)
                 } else {
                     should_unpark = true;
                 }
             }
-            State::Shooting(cooldown) => {
-                if rl.get_frame_time() < *cooldown {
-                    self.state = State::Shooting(cooldown - rl.get_frame_time())
+            State::Scanning(cooldown) => {
+                if dt < *cooldown {
+                    self.state = State::Scanning(cooldown - dt)
+                } else {
+                    should_unpark = true;
+                }
+            }
+            State::Sleeping(time) => {
+                if dt < *time {
+                    self.state = State::Sleeping(time - dt)
                 } else {
                     should_unpark = true;
                 }
             }
+            State::Crashed(_) if self.rules.crash_eliminates => return,
+            State::Crashed(_) => (),
             State::Destroyed => return,
         }
 
-        if collision_frame.check_collision(vec!["bullet", "rock"], self.get_shape()) {
-            self.state = State::Destroyed;
-        } else {
-            let mut raycast_lock = self.handle.lock().unwrap();
-            *raycast_lock = self.make_handle(collision_frame);
-            drop(raycast_lock);
+        self.advance_motion(dt);
+        match self.state {
+            State::Moving(_) if self.move_left == 0.0 => should_unpark = true,
+            State::Moving(_) => self.state = State::Moving(self.move_left),
+            State::Turning(_) if self.turn_left == 0.0 => should_unpark = true,
+            State::Turning(_) => self.state = State::Turning(self.turn_left),
+            _ => (),
+        }
 
-            if should_unpark {
-                self.next();
+        self.invulnerable = (self.invulnerable - dt).max(0.0);
+        if self.reload > 0.0 {
+            self.reload -= dt;
+            if self.reload <= 0.0 {
+                self.reload = 0.0;
+                self.ammo = self.rules.clip_size;
             }
         }
+        self.should_unpark = should_unpark;
     }
 
-    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets) {
-        if let State::Destroyed = self.state {
-            d.draw_texture_pro(
-                &assets.ship_dead,
-                Rectangle::new(0.0, 0.0, 50.0, 50.0),
-                Rectangle::new(self.pos.x, self.pos.y, 50.0, 50.0),
-                Vector2::new(25.0, 25.0),
-                self.rotation + 90.0,
-                Color::WHITE,
-            );
-        } else {
-            d.draw_texture_pro(
-                &assets.ship,
-                Rectangle::new(0.0, 0.0, 50.0, 50.0),
-                Rectangle::new(self.pos.x, self.pos.y, 50.0, 50.0),
-                Vector2::new(25.0, 25.0),
-                self.rotation + 90.0,
-                Color::WHITE,
-            );
+    fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, alpha: f32) {
+        ShipSnapshot {
+            pos: self.prev_pos.lerp(self.pos, alpha),
+            rotation: object::lerp_rotation(self.prev_rotation, self.rotation, alpha),
+            turret: object::lerp_rotation(self.prev_turret, self.turret, alpha),
+            hp: self.hp,
+            max_hp: self.rules.ship_hp,
+            state: self.state.clone(),
         }
-        d.draw_text(
-            &self.name,
-            self.pos.x as i32 - text::measure_text(&self.name, 18) / 2,
-            self.pos.y as i32 - 50,
-            18,
-            Color::GREEN,
-        )
+        .draw(d, assets, &self.name)
     }
 
-    fn get_shape(&self) -> Circle {
-        if let State::Destroyed = &self.state {
-            (Vector2::zero(), 0.0)
+    fn get_shape(&self) -> Shape {
+        if self.is_eliminated() {
+            Shape::empty()
         } else {
-            (self.pos, 20.0)
+            Shape::Circle(self.pos, Self::RADIUS)
         }
     }
+
+    fn get_prev_pos(&self) -> Vector2 {
+        self.prev_pos
+    }
+
+    fn get_owner(&self) -> Option<usize> {
+        Some(self.id)
+    }
 }
 
 impl Debug for Ship {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Ship")
             .field("pos", &self.pos)
             .field("rotation", &self.rotation)
@@ -1,5 +1,8 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    mem,
+    rc::Rc,
     sync::{mpsc::Sender, Arc, Mutex},
     thread,
 };
@@ -12,12 +15,72 @@ use bean_script::{
     scope::{function::Function, ScopeRef},
 };
 
-use super::ShipHandle;
+use super::{Ship, ShipHandle};
+use crate::world::World;
 
 pub enum APIRequest {
     Move(f32),
     Turn(f32),
+    /// Starts moving without waiting for the move to finish.
+    MoveAsync(f32),
+    /// Starts turning without waiting for the turn to finish.
+    TurnAsync(f32),
+    /// Cancels any move or turn in progress.
+    Stop,
+    TurretTurn(f32),
     Shoot,
+    /// Field of view in degrees and range in pixels.
+    Scan(f32, f32),
+    /// Angle in degrees, and whether it's absolute rather than relative to the ship's heading.
+    RaycastAt(f32, bool),
+    Sleep(f32),
+    /// Sent by the script thread when the script stops with an error.
+    Crash(String),
+    /// Sent by the script thread when the script runs to the end.
+    Finished,
+    /// Sent when the script has run past its step budget without waiting on the ship.
+    Throttle,
+}
+
+/// Something that happened to a ship, passed on to the script's `on` handlers.
+pub enum Event {
+    /// The ship took this much damage.
+    Hit(f32),
+    /// Someone else's bullet came within this many pixels.
+    BulletNear(f32),
+    /// The ship ran into the edge of the arena.
+    CollideWall,
+    /// Another ship was destroyed. Holds its name.
+    ShipDestroyed(String),
+}
+
+impl Event {
+    const NAMES: [&'static str; 4] = ["hit", "bullet_near", "collide_wall", "ship_destroyed"];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Hit(_) => "hit",
+            Event::BulletNear(_) => "bullet_near",
+            Event::CollideWall => "collide_wall",
+            Event::ShipDestroyed(_) => "ship_destroyed",
+        }
+    }
+
+    fn args(&self) -> Vec<Data> {
+        match self {
+            Event::Hit(damage) => vec![Data::Number(*damage as f64)],
+            Event::BulletNear(dist) => vec![Data::Number(*dist as f64)],
+            Event::CollideWall => Vec::new(),
+            Event::ShipDestroyed(name) => vec![Data::String(name.clone())],
+        }
+    }
+}
+
+/// Callbacks registered with `robot_api.on`, by event name.
+#[derive(Default)]
+pub struct Handlers {
+    callbacks: HashMap<String, Vec<Function>>,
+    dispatching: bool,
 }
 
 fn get_sender(registry: &ModuleRegistry) -> Result<&Sender<APIRequest>, Error> {
@@ -35,6 +98,21 @@ fn get_sender(registry: &ModuleRegistry) -> Result<&Sender<APIRequest>, Error> {
         ))
 }
 
+fn get_handlers(registry: &ModuleRegistry) -> Result<&Rc<RefCell<Handlers>>, Error> {
+    registry
+        .metadata
+        .get("handlers")
+        .ok_or(Error::new(
+            "Couldn't access API event handlers.",
+            ErrorSource::Internal,
+        ))?
+        .downcast_ref::<Rc<RefCell<Handlers>>>()
+        .ok_or(Error::new(
+            "API event handlers were incorrect type.",
+            ErrorSource::Internal,
+        ))
+}
+
 fn get_mutex(registry: &ModuleRegistry) -> Result<Arc<Mutex<ShipHandle>>, Error> {
     registry
         .metadata
@@ -51,18 +129,139 @@ fn get_mutex(registry: &ModuleRegistry) -> Result<Arc<Mutex<ShipHandle>>, Error>
         .map(Arc::clone)
 }
 
+fn get_steps(registry: &ModuleRegistry) -> Result<&Rc<Cell<u32>>, Error> {
+    registry
+        .metadata
+        .get("steps")
+        .ok_or(Error::new(
+            "Couldn't access API step counter.",
+            ErrorSource::Internal,
+        ))?
+        .downcast_ref::<Rc<Cell<u32>>>()
+        .ok_or(Error::new(
+            "API step counter was incorrect type.",
+            ErrorSource::Internal,
+        ))
+}
+
+/// Builds a value with one of the runtime's collection constructors, `list` or `map`. Their
+/// types aren't exported, so this goes through the script's own scope.
+fn make_collection(scope: &ScopeRef, kind: &str, items: Vec<Data>) -> Result<Data, Error> {
+    let constructor = RefCell::borrow(scope).get_function(kind).ok_or(Error::new(
+        &format!("Couldn't find the runtime's {} function.", kind),
+        ErrorSource::Internal,
+    ))?;
+    constructor.call(items, None, Rc::clone(scope))
+}
+
+/// Parks until the ship has carried out a request, then runs handlers for anything that
+/// happened in the meantime. Fails once the ship has been stopped, so the error unwinds the
+/// script instead of leaving its thread parked forever. Callers have to let go of the
+/// registry and their module first: handlers run in between, and things like `use` need to
+/// borrow them mutably.
+fn wait_for_ship(scope: &ScopeRef) -> Result<(), Error> {
+    thread::park();
+
+    let binding = RefCell::borrow(scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Internal,
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    get_steps(&registry)?.set(0);
+    let mutex = get_mutex(&registry)?;
+    let handlers = Rc::clone(get_handlers(&registry)?);
+    drop(registry);
+    drop(borrowed);
+
+    if mutex.lock().unwrap().stopped {
+        return Err(Error::new("Ship was destroyed.", ErrorSource::Internal));
+    }
+    dispatch_events(&mutex, &handlers, scope)
+}
+
+fn dispatch_events(
+    mutex: &Mutex<ShipHandle>,
+    handlers: &RefCell<Handlers>,
+    scope: &ScopeRef,
+) -> Result<(), Error> {
+    // Actions inside a handler are safe points too, but its events wait until it's done.
+    if RefCell::borrow(handlers).dispatching {
+        return Ok(());
+    }
+    let events = mem::take(&mut mutex.lock().unwrap().events);
+
+    handlers.borrow_mut().dispatching = true;
+    let result = events.into_iter().try_for_each(|event| {
+        let callbacks = RefCell::borrow(handlers)
+            .callbacks
+            .get(event.name())
+            .cloned()
+            .unwrap_or_default();
+        callbacks.iter().try_for_each(|callback| {
+            callback
+                .call(event.args(), None, Rc::clone(scope))
+                .map(|_| ())
+        })
+    });
+    handlers.borrow_mut().dispatching = false;
+
+    result
+}
+
+/// Counts a step the script took without waiting on its ship, and holds it back until the
+/// next tick once it's past `budget`. `steps` is the registry's step counter, passed in
+/// directly because this runs before every builtin call.
+pub fn count_step(scope: &ScopeRef, steps: &Cell<u32>, budget: u32) -> Result<(), Error> {
+    steps.set(steps.get() + 1);
+    if steps.get() <= budget {
+        return Ok(());
+    }
+
+    let binding = RefCell::borrow(scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Internal,
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    get_sender(&registry)?
+        .send(APIRequest::Throttle)
+        .map_err(|_| Error::new("Failed to send API request.", ErrorSource::Internal))?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(scope)
+}
+
 pub fn construct(module: &mut ModuleBuilder) {
     module
         .function("move", fn_move)
         .function("turn", fn_turn)
+        .function("move_async", fn_move_async)
+        .function("turn_async", fn_turn_async)
+        .function("stop", fn_stop)
+        .function("is_busy", fn_is_busy)
+        .function("turret_turn", fn_turret_turn)
         .function("shoot", fn_shoot)
+        .function("scan", fn_scan)
         .function("raycast", fn_raycast)
         .function("raycast_dist", fn_raycast_dist)
+        .function("raycast_at", fn_raycast_at)
         .function("x", fn_x)
         .function("y", fn_y)
         .function("rayhit_x", fn_rayhit_x)
         .function("rayhit_y", fn_rayhit_y)
-        .function("rotation", fn_rotation);
+        .function("rotation", fn_rotation)
+        .function("turret_rotation", fn_turret_rotation)
+        .function("health", fn_health)
+        .function("ammo", fn_ammo)
+        .function("wait", fn_wait)
+        .function("on", fn_on);
 }
 
 fn fn_move(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
@@ -84,9 +283,14 @@ fn fn_move(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Dat
             ErrorSource::Builtin(String::from("robot_api:move")),
         )
     })?;
-    thread::park();
+    let mutex = get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:move")))?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:move")))?;
 
-    Ok(Data::None)
+    let mutex_lock = mutex.lock().unwrap();
+
+    Ok(Data::Boolean(!mutex_lock.move_blocked))
 }
 
 fn fn_turn(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
@@ -111,7 +315,132 @@ fn fn_turn(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Dat
                 ErrorSource::Builtin(String::from("robot_api:turn")),
             )
         })?;
-    thread::park();
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:turn")))?;
+
+    Ok(Data::None)
+}
+
+fn fn_move_async(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Number(d), "Expected a number, but instead got a {}.", "robot_api:move_async");
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:move_async")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    let sender =
+        get_sender(&registry).trace(ErrorSource::Builtin(String::from("robot_api:move_async")))?;
+    sender.send(APIRequest::MoveAsync(*d as f32)).map_err(|_| {
+        Error::new(
+            "Failed to send API request.",
+            ErrorSource::Builtin(String::from("robot_api:move_async")),
+        )
+    })?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:move_async")))?;
+
+    Ok(Data::None)
+}
+
+fn fn_turn_async(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Number(d), "Expected a number, but instead got a {}.", "robot_api:turn_async");
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:turn_async")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    let sender =
+        get_sender(&registry).trace(ErrorSource::Builtin(String::from("robot_api:turn_async")))?;
+    sender.send(APIRequest::TurnAsync(*d as f32)).map_err(|_| {
+        Error::new(
+            "Failed to send API request.",
+            ErrorSource::Builtin(String::from("robot_api:turn_async")),
+        )
+    })?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:turn_async")))?;
+
+    Ok(Data::None)
+}
+
+fn fn_stop(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:stop")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    let sender =
+        get_sender(&registry).trace(ErrorSource::Builtin(String::from("robot_api:stop")))?;
+    sender.send(APIRequest::Stop).map_err(|_| {
+        Error::new(
+            "Failed to send API request.",
+            ErrorSource::Builtin(String::from("robot_api:stop")),
+        )
+    })?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:stop")))?;
+
+    Ok(Data::None)
+}
+
+fn fn_is_busy(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:is_busy")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+
+    let mutex =
+        get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:is_busy")))?;
+    let mutex_lock = mutex.lock().unwrap();
+
+    Ok(Data::Boolean(mutex_lock.busy))
+}
+
+fn fn_turret_turn(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Number(d), "Expected a number, but instead got a {}.", "robot_api:turret_turn");
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:turret_turn")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    let sender =
+        get_sender(&registry).trace(ErrorSource::Builtin(String::from("robot_api:turret_turn")))?;
+    sender
+        .send(APIRequest::TurretTurn(*d as f32))
+        .map_err(|_| {
+            Error::new(
+                "Failed to send API request.",
+                ErrorSource::Builtin(String::from("robot_api:turret_turn")),
+            )
+        })?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:turret_turn")))?;
 
     Ok(Data::None)
 }
@@ -134,7 +463,183 @@ fn fn_shoot(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data
             ErrorSource::Builtin(String::from("robot_api:shoot")),
         )
     })?;
-    thread::park();
+    let mutex =
+        get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:shoot")))?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:shoot")))?;
+
+    let mutex_lock = mutex.lock().unwrap();
+
+    Ok(Data::Boolean(mutex_lock.shot_fired))
+}
+
+fn fn_scan(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Number(fov), "Expected a number, but instead got a {}.", "robot_api:scan");
+    arg_check!(args.get(1).unwrap_or(&Data::None) => Data::Number(range), "Expected a number, but instead got a {}.", "robot_api:scan");
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:scan")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    let sender =
+        get_sender(&registry).trace(ErrorSource::Builtin(String::from("robot_api:scan")))?;
+    sender
+        .send(APIRequest::Scan(
+            (*fov as f32).clamp(0.0, 360.0),
+            (*range as f32).clamp(0.0, Ship::MAX_SCAN_RANGE),
+        ))
+        .map_err(|_| {
+            Error::new(
+                "Failed to send API request.",
+                ErrorSource::Builtin(String::from("robot_api:scan")),
+            )
+        })?;
+    let mutex = get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:scan")))?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:scan")))?;
+
+    let contacts = mutex.lock().unwrap().contacts.clone();
+
+    let contacts = contacts
+        .into_iter()
+        .map(|contact| {
+            make_collection(
+                &scope,
+                "map",
+                vec![
+                    Data::String(String::from("kind")),
+                    Data::String(String::from(contact.kind)),
+                    Data::String(String::from("distance")),
+                    Data::Number(contact.distance as f64),
+                    Data::String(String::from("bearing")),
+                    Data::Number(contact.bearing as f64),
+                ],
+            )
+        })
+        .collect::<Result<Vec<Data>, Error>>()
+        .trace(ErrorSource::Builtin(String::from("robot_api:scan")))?;
+    make_collection(&scope, "list", contacts)
+        .trace(ErrorSource::Builtin(String::from("robot_api:scan")))
+}
+
+fn fn_raycast_at(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Number(angle), "Expected a number, but instead got a {}.", "robot_api:raycast_at");
+    let absolute = matches!(args.get(1), Some(Data::Boolean(true)));
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:raycast_at")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    let sender =
+        get_sender(&registry).trace(ErrorSource::Builtin(String::from("robot_api:raycast_at")))?;
+    sender
+        .send(APIRequest::RaycastAt(*angle as f32, absolute))
+        .map_err(|_| {
+            Error::new(
+                "Failed to send API request.",
+                ErrorSource::Builtin(String::from("robot_api:raycast_at")),
+            )
+        })?;
+    let mutex =
+        get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:raycast_at")))?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:raycast_at")))?;
+
+    let hit = mutex.lock().unwrap().ray_at.clone();
+
+    let mut items = vec![
+        Data::String(String::from("kind")),
+        Data::String(String::from(hit.kind)),
+        Data::String(String::from("distance")),
+        Data::Number(hit.distance as f64),
+        Data::String(String::from("x")),
+        Data::Number(hit.point.x as f64),
+        Data::String(String::from("y")),
+        Data::Number(hit.point.y as f64),
+        Data::String(String::from("normal")),
+        Data::Number(hit.normal.y.atan2(hit.normal.x).to_degrees() as f64),
+    ];
+    if let Some(id) = hit.id {
+        items.push(Data::String(String::from("id")));
+        items.push(Data::Number(id as f64));
+    }
+    make_collection(&scope, "map", items)
+        .trace(ErrorSource::Builtin(String::from("robot_api:raycast_at")))
+}
+
+/// How long to sleep for so the ship hands control back after `ticks` ticks, rounded to a
+/// whole number of at least one. Sleeping counts down by whole ticks, so this aims between
+/// the last two to stop float error from adding an extra one.
+fn tick_time(ticks: f64) -> f32 {
+    (ticks.max(1.0).round() as f32 - 0.5) * World::TICK
+}
+
+fn fn_wait(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Number(ticks), "Expected number of ticks, but instead got {}.", "robot_api:wait");
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:wait")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    let sender =
+        get_sender(&registry).trace(ErrorSource::Builtin(String::from("robot_api:wait")))?;
+    sender
+        .send(APIRequest::Sleep(tick_time(*ticks)))
+        .map_err(|_| {
+            Error::new(
+                "Failed to send API request.",
+                ErrorSource::Builtin(String::from("robot_api:wait")),
+            )
+        })?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("robot_api:wait")))?;
+
+    Ok(Data::None)
+}
+
+/// Stand-in for the runtime's `sleep` in lockstep matches, which waits in simulated time,
+/// rounded to whole ticks.
+pub fn fn_sleep(args: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Number(ms), "Expected number of milliseconds, but instead got {}.", "sleep");
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("sleep")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+    let sender = get_sender(&registry).trace(ErrorSource::Builtin(String::from("sleep")))?;
+    sender
+        .send(APIRequest::Sleep(tick_time(
+            ms / 1000.0 / World::TICK as f64,
+        )))
+        .map_err(|_| {
+            Error::new(
+                "Failed to send API request.",
+                ErrorSource::Builtin(String::from("sleep")),
+            )
+        })?;
+    drop(registry);
+    drop(borrowed);
+    wait_for_ship(&scope).trace(ErrorSource::Builtin(String::from("sleep")))?;
 
     Ok(Data::None)
 }
@@ -226,10 +731,7 @@ fn fn_rayhit_x(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<D
         get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:raycast")))?;
     let mutex_lock = mutex.lock().unwrap();
 
-    Ok(Data::Number(
-        (mutex_lock.pos.x + mutex_lock.rotation.to_radians().cos() * mutex_lock.raycast_dist)
-            as f64,
-    ))
+    Ok(Data::Number(mutex_lock.rayhit.x as f64))
 }
 
 fn fn_rayhit_y(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
@@ -247,10 +749,7 @@ fn fn_rayhit_y(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<D
         get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:raycast")))?;
     let mutex_lock = mutex.lock().unwrap();
 
-    Ok(Data::Number(
-        (mutex_lock.pos.y + mutex_lock.rotation.to_radians().sin() * mutex_lock.raycast_dist)
-            as f64,
-    ))
+    Ok(Data::Number(mutex_lock.rayhit.y as f64))
 }
 
 fn fn_rotation(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
@@ -270,3 +769,90 @@ fn fn_rotation(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<D
 
     Ok(Data::Number(mutex_lock.rotation as f64))
 }
+
+fn fn_turret_rotation(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:turret_rotation")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+
+    let mutex = get_mutex(&registry).trace(ErrorSource::Builtin(String::from(
+        "robot_api:turret_rotation",
+    )))?;
+    let mutex_lock = mutex.lock().unwrap();
+
+    Ok(Data::Number(mutex_lock.turret_rotation as f64))
+}
+
+fn fn_health(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:health")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+
+    let mutex =
+        get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:health")))?;
+    let mutex_lock = mutex.lock().unwrap();
+
+    Ok(Data::Number(mutex_lock.health as f64))
+}
+
+fn fn_ammo(_a: Vec<Data>, _b: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:ammo")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+
+    let mutex = get_mutex(&registry).trace(ErrorSource::Builtin(String::from("robot_api:ammo")))?;
+    let mutex_lock = mutex.lock().unwrap();
+
+    Ok(Data::Number(mutex_lock.ammo as f64))
+}
+
+fn fn_on(args: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::String(event), "Expected an event name, but instead got a {}.", "robot_api:on");
+    if !Event::NAMES.contains(&event.as_str()) {
+        return Err(Error::new(
+            &format!("Unknown event {}.", event),
+            ErrorSource::Builtin(String::from("robot_api:on")),
+        ));
+    }
+    let callback = body_fn.ok_or(Error::new(
+        "Expected body fn.",
+        ErrorSource::Builtin(String::from("robot_api:on")),
+    ))?;
+    let binding = RefCell::borrow(&scope).get_file_module().ok_or(Error::new(
+        "Cannot connect to api outside of module.",
+        ErrorSource::Builtin(String::from("robot_api:on")),
+    ))?;
+    let borrowed = RefCell::borrow(&binding);
+    let registry = RefCell::borrow(
+        &as_type!(borrowed => CustomModule, "Returned non-CustomModule from get_file_module")
+            .registry,
+    );
+
+    get_handlers(&registry)
+        .trace(ErrorSource::Builtin(String::from("robot_api:on")))?
+        .borrow_mut()
+        .callbacks
+        .entry(event.clone())
+        .or_default()
+        .push(callback);
+
+    Ok(Data::None)
+}
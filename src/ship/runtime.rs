@@ -0,0 +1,248 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Component, Path},
+    rc::Rc,
+};
+
+use bean_script::{
+    arg_check,
+    data::Data,
+    error::{Error, ErrorSource},
+    modules::{registry::ModuleRegistry, BuiltinModule, ModuleBuilder},
+    scope::{function::Function, Scope, ScopeRef},
+    util::make_ref,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Rebuilds the registry's runtime module with every builtin it already has, then lets
+/// `patches` add or replace functions. The original builtins are passed along so patches can
+/// wrap them. Every module loaded through the registry shares the runtime, so this is the
+/// only way to change a builtin for imported files too.
+pub fn patch(
+    registry: &ModuleRegistry,
+    patches: impl FnOnce(&mut ModuleBuilder, &HashMap<String, Function>),
+) {
+    let runtime = registry.runtime();
+    let functions = RefCell::borrow(&runtime).get_function_list();
+
+    let patched = BuiltinModule::new(
+        |module| {
+            for (name, function) in &functions {
+                if let Function::BuiltIn { callback } = function {
+                    let callback = Rc::clone(callback);
+                    module.function(name, move |args, body_fn, scope| {
+                        callback(args, body_fn, scope)
+                    });
+                }
+            }
+            patches(module, &functions);
+        },
+        registry.features,
+    );
+
+    *runtime.borrow_mut() = patched;
+}
+
+/// Replaces `rand` with a version driven by a seeded generator, so scripts are as
+/// reproducible as the arena they run in.
+pub fn seeded_rand(module: &mut ModuleBuilder, seed: u64) {
+    let rng = make_ref(StdRng::seed_from_u64(seed));
+    module.function("rand", move |args, _b, _s| {
+        fn_rand(args, &mut rng.borrow_mut())
+    });
+}
+
+fn fn_rand(args: Vec<Data>, rng: &mut StdRng) -> Result<Data, Error> {
+    match args.len() {
+        0 => Ok(Data::Number(rng.gen())),
+        1 => {
+            arg_check!(args[0] => Data::Number(max), "Expected number, but got {} instead.", "rand");
+            Ok(Data::Number((rng.gen::<f64>() * max).floor()))
+        }
+        _ => {
+            arg_check!(args[0] => Data::Number(min), "Expected number, but got {} instead.", "rand");
+            arg_check!(args[1] => Data::Number(max), "Expected number, but got {} instead.", "rand");
+            Ok(Data::Number((rng.gen::<f64>() * (max - min)).floor() + min))
+        }
+    }
+}
+
+/// Makes every builtin call `step` before it runs, and replaces `while` and `repeat` with
+/// versions that call it before every iteration too, so a script that never waits on its
+/// ship can be held back however it keeps itself busy. Functions the script defines can't be
+/// wrapped, but anything they do goes through builtins, so recursion and `for` over a list
+/// are counted as well.
+pub fn counted(
+    module: &mut ModuleBuilder,
+    functions: &HashMap<String, Function>,
+    step: impl Fn(&ScopeRef) -> Result<(), Error> + 'static,
+) {
+    let step = Rc::new(step);
+    for (name, function) in functions {
+        if let Function::BuiltIn { callback } = function {
+            let callback = Rc::clone(callback);
+            let step = Rc::clone(&step);
+            module.function(name, move |args, body_fn, scope| {
+                step(&scope)?;
+                callback(args, body_fn, scope)
+            });
+        }
+    }
+
+    let while_step = Rc::clone(&step);
+    module
+        .function("while", move |args, body_fn, scope| {
+            while_step(&scope)?;
+            fn_while(args, body_fn, scope, &*while_step)
+        })
+        .function("repeat", move |args, body_fn, scope| {
+            step(&scope)?;
+            fn_repeat(args, body_fn, scope, &*step)
+        });
+}
+
+fn fn_while(
+    _a: Vec<Data>,
+    body_fn: Option<Function>,
+    scope: ScopeRef,
+    step: &dyn Fn(&ScopeRef) -> Result<(), Error>,
+) -> Result<Data, Error> {
+    let body_fn = body_fn.ok_or(Error::new(
+        "Expected body fn.",
+        ErrorSource::Builtin(String::from("while")),
+    ))?;
+
+    loop {
+        step(&scope)?;
+        let v = body_fn.call_direct(Vec::new(), None, Rc::clone(&scope))?;
+        if Data::Boolean(false) == v {
+            break;
+        }
+    }
+
+    Ok(Data::None)
+}
+
+fn fn_repeat(
+    args: Vec<Data>,
+    body_fn: Option<Function>,
+    scope: ScopeRef,
+    step: &dyn Fn(&ScopeRef) -> Result<(), Error>,
+) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Number(n), "Expected integer, but instead got {}.", "repeat");
+    let body_fn = body_fn.ok_or(Error::new(
+        "Expected body fn.",
+        ErrorSource::Builtin(String::from("repeat")),
+    ))?;
+
+    for _ in 0..*n as usize {
+        step(&scope)?;
+        let v = body_fn.call_direct(Vec::new(), None, Rc::clone(&scope))?;
+        if Data::Boolean(false) == v {
+            break;
+        }
+    }
+
+    Ok(Data::None)
+}
+
+/// Modules a sandboxed script is allowed to `use` by name.
+const SANDBOX_MODULES: [&str; 2] = ["std", "robot_api"];
+
+/// Locks down a script for competitive matches: `use` only reaches whitelisted modules and,
+/// if `local_imports` is set, files below the directory the script's module is rooted at.
+/// Wall-clock `sleep` is replaced by `robot_api.wait`.
+pub fn sandbox(
+    module: &mut ModuleBuilder,
+    functions: &HashMap<String, Function>,
+    local_imports: bool,
+) {
+    let Some(Function::BuiltIn { callback: fn_use }) = functions.get("use").cloned() else {
+        return;
+    };
+
+    module
+        .function("use", move |args, body_fn, scope| {
+            arg_check!(args.first().unwrap_or(&Data::None) => Data::String(mod_id), "Expected string, but instead got {}.", "use");
+            let path = mod_id.split(':').next().unwrap_or_default();
+
+            if let Some(local) = path.strip_prefix("./") {
+                if !local_imports {
+                    return Err(Error::new(
+                        "Local imports are disabled in sandboxed matches.",
+                        ErrorSource::Builtin(String::from("use")),
+                    ));
+                }
+                if Path::new(local)
+                    .components()
+                    .any(|component| !matches!(component, Component::Normal(_)))
+                {
+                    return Err(Error::new(
+                        &format!("Can't import {} from outside the library directory.", path),
+                        ErrorSource::Builtin(String::from("use")),
+                    ));
+                }
+            } else if !SANDBOX_MODULES.contains(&path) {
+                return Err(Error::new(
+                    &format!("Module {} isn't available in sandboxed matches.", path),
+                    ErrorSource::Builtin(String::from("use")),
+                ));
+            }
+
+            fn_use(args, body_fn, scope)
+        })
+        .function("sleep", |_a, _b, _s| {
+            Err(Error::new(
+                "sleep is disabled in sandboxed matches. Use robot_api.wait instead.",
+                ErrorSource::Builtin(String::from("sleep")),
+            ))
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, path::PathBuf};
+
+    use bean_script::modules::{registry::RegistryFeatures, CustomModule};
+
+    use super::*;
+
+    #[test]
+    fn counts_busy_script_without_loops() {
+        const LIMIT: u32 = 1000;
+
+        let registry = ModuleRegistry::new(RegistryFeatures::default());
+        let steps = Rc::new(Cell::new(0u32));
+        let counter = Rc::clone(&steps);
+        patch(&registry, |module, functions| {
+            counted(module, functions, move |_| {
+                counter.set(counter.get() + 1);
+                if counter.get() > LIMIT {
+                    Err(Error::new("Out of steps.", ErrorSource::Internal))
+                } else {
+                    Ok(())
+                }
+            });
+        });
+
+        // Busy for far longer than the budget, through recursion and `for` over a list
+        // rather than `while` or `repeat`.
+        let source = "
+            fn(<fib>): {
+                if(lt(p(0), 2)): {
+                    super.return(p(0))
+                } else: {
+                    super.return(+(fib(-(p(0), 1)), fib(-(p(0), 2))))
+                }
+            }
+            let(<l>): list(25, 25, 25)
+            l.for(<n>): { fib(n) }
+        ";
+        let scope = make_ref(CustomModule::new(make_ref(registry), PathBuf::new()));
+        let result = bean_script::interpret(String::from(source), scope);
+
+        assert!(result.unwrap_err().to_string().contains("Out of steps."));
+        assert_eq!(steps.get(), LIMIT + 1);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{fs, path::Path};
+
+use itertools::Itertools;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{options::Options, world::World};
+
+#[derive(Default)]
+struct Standing {
+    name: String,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+    kills: u32,
+    crashes: u32,
+}
+
+/// Plays every group of `options.group_size` scripts found in the tournament directory
+/// against each other headlessly, then prints the standings.
+pub fn run(options: &Options, seed: u64) -> Result<(), String> {
+    let dir = &options.paths[0];
+    let mut bots: Vec<String> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir, e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bean"))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    bots.sort();
+
+    if bots.len() < options.group_size {
+        return Err(format!(
+            "Found {} robot scripts in {}, but groups need {}.",
+            bots.len(),
+            dir,
+            options.group_size
+        ));
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut standings: Vec<Standing> = bots
+        .iter()
+        .map(|path| Standing {
+            name: Path::new(path)
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+            ..Default::default()
+        })
+        .collect();
+
+    for round in 1..=options.rounds {
+        for group in (0..bots.len()).combinations(options.group_size) {
+            let paths: Vec<String> = group.iter().map(|&bot| bots[bot].clone()).collect();
+            let match_seed = rng.gen();
+
+            let mut world = World::new(&paths, match_seed, &options.rules);
+            world.play_out(options.time_limit);
+
+            let winner = world.winner().map(|ship| ship.get_id());
+            for (ship, &bot) in world.ships.iter().zip(&group) {
+                let standing = &mut standings[bot];
+                standing.kills += ship.kills();
+                if ship.error().is_some() {
+                    standing.crashes += 1;
+                }
+                match winner {
+                    Some(id) if id == ship.get_id() => standing.wins += 1,
+                    Some(_) => standing.losses += 1,
+                    None => standing.draws += 1,
+                }
+            }
+
+            println!(
+                "round {}: {} -> {} (seed {})",
+                round,
+                world.ships.iter().map(|ship| &ship.name).join(" vs "),
+                world
+                    .winner()
+                    .map(|ship| ship.name.clone() + " won")
+                    .unwrap_or(String::from("draw")),
+                match_seed
+            );
+            for incident in world.incidents() {
+                println!("  {}", incident);
+            }
+        }
+    }
+
+    standings.sort_by(|a, b| {
+        (b.wins, b.draws, b.kills)
+            .cmp(&(a.wins, a.draws, a.kills))
+            .then(a.name.cmp(&b.name))
+    });
+
+    println!();
+    println!(
+        "{:<16} {:>5} {:>7} {:>6} {:>6} {:>8}",
+        "bot", "wins", "losses", "draws", "kills", "crashes"
+    );
+    for standing in standings {
+        println!(
+            "{:<16} {:>5} {:>7} {:>6} {:>6} {:>8}",
+            standing.name,
+            standing.wins,
+            standing.losses,
+            standing.draws,
+            standing.kills,
+            standing.crashes
+        );
+    }
+
+    Ok(())
+}
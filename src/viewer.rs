@@ -0,0 +1,165 @@
+use raylib::prelude::*;
+
+use crate::world::World;
+
+/// Playback controls over a timeline of ticks, shared by the live game's rewind buffer and
+/// the replay viewer.
+///
+/// - `space` pauses and resumes
+/// - `,` and `.` step back and forward one tick, where stepping forward at the end of a live
+///   game's timeline runs the world for a tick
+/// - `left` and `right` scrub through the match, faster while holding `shift`
+/// - `up` and `down` double and halve the playback speed
+/// - clicking or dragging on the timeline seeks
+pub struct Viewer {
+    pub paused: bool,
+    speed: f32,
+    /// Position in the timeline in ticks. Fractional so slow playback still advances.
+    frame: f32,
+    /// Whether the last update asked to step forward from the end of the timeline.
+    step_past_end: bool,
+}
+
+impl Viewer {
+    const MIN_SPEED: f32 = 0.25;
+    const MAX_SPEED: f32 = 8.0;
+    /// Seconds of match time scrubbed per second an arrow key is held.
+    const SCRUB_SPEED: f32 = 5.0;
+
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            frame: 0.0,
+            step_past_end: false,
+        }
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame as usize
+    }
+
+    pub fn is_at_end(&self, len: usize) -> bool {
+        self.frame() + 1 >= len
+    }
+
+    /// Whether playback is plain real time at the end of the timeline, where the live game
+    /// doesn't need to show any controls.
+    pub fn is_live(&self, len: usize) -> bool {
+        self.is_at_end(len) && !self.paused && self.speed == 1.0
+    }
+
+    /// Whether stepping forward was pressed at the end of the timeline, so a live game should
+    /// run one more tick.
+    pub fn wants_step(&self) -> bool {
+        self.step_past_end
+    }
+
+    pub fn seek_end(&mut self, len: usize) {
+        self.frame = len.saturating_sub(1) as f32;
+    }
+
+    /// Handles input and plays through a timeline of `len` ticks. Returns the match time, in
+    /// seconds, that playback wanted to go past the end of the timeline.
+    pub fn update(&mut self, rl: &RaylibHandle, len: usize) -> f32 {
+        let last = len.saturating_sub(1) as f32;
+        let dt = rl.get_frame_time();
+
+        if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
+            self.paused = !self.paused;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_UP) {
+            self.speed = (self.speed * 2.0).min(Self::MAX_SPEED);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_DOWN) {
+            self.speed = (self.speed / 2.0).max(Self::MIN_SPEED);
+        }
+        self.step_past_end = false;
+        if rl.is_key_pressed(KeyboardKey::KEY_PERIOD) {
+            self.paused = true;
+            self.frame = self.frame.floor() + 1.0;
+            self.step_past_end = self.frame > last;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_COMMA) {
+            self.paused = true;
+            self.frame = self.frame.floor() - 1.0;
+        }
+
+        let scrub = if rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+            Self::SCRUB_SPEED * 4.0
+        } else {
+            Self::SCRUB_SPEED
+        } * dt
+            / World::TICK;
+        if rl.is_key_down(KeyboardKey::KEY_RIGHT) {
+            self.frame += scrub;
+        }
+        if rl.is_key_down(KeyboardKey::KEY_LEFT) {
+            self.frame -= scrub;
+        }
+
+        let timeline = Self::timeline(rl.get_screen_width(), rl.get_screen_height());
+        let mouse = rl.get_mouse_position();
+        if rl.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON)
+            && mouse.y >= timeline.y - 10.0
+            && mouse.y <= timeline.y + timeline.height + 10.0
+        {
+            self.frame = (mouse.x - timeline.x) / timeline.width * last;
+        }
+
+        let mut overflow = 0.0;
+        if !self.paused {
+            self.frame += dt * self.speed / World::TICK;
+            if self.frame > last {
+                overflow = (self.frame - last) * World::TICK;
+            }
+        }
+        self.frame = self.frame.clamp(0.0, last);
+
+        overflow
+    }
+
+    /// Draws the timeline and playback status. `tick` is the match tick being shown.
+    pub fn draw(&self, d: &mut RaylibDrawHandle, len: usize, tick: u64) {
+        let timeline = Self::timeline(d.get_screen_width(), d.get_screen_height());
+        let progress = if len > 1 {
+            self.frame / (len - 1) as f32
+        } else {
+            1.0
+        };
+
+        d.draw_rectangle_rec(timeline, Color::DARKGRAY);
+        d.draw_rectangle_rec(
+            Rectangle::new(
+                timeline.x,
+                timeline.y,
+                timeline.width * progress,
+                timeline.height,
+            ),
+            Color::GREEN,
+        );
+
+        d.draw_text(
+            &format!(
+                "{} {}x  tick {} ({:.2}s)",
+                if self.paused { "paused" } else { "playing" },
+                self.speed,
+                tick,
+                tick as f32 * World::TICK
+            ),
+            10,
+            timeline.y as i32 - 28,
+            20,
+            Color::GREEN,
+        );
+    }
+
+    fn timeline(screen_width: i32, screen_height: i32) -> Rectangle {
+        Rectangle::new(
+            10.0,
+            screen_height as f32 - 60.0,
+            screen_width as f32 - 20.0,
+            8.0,
+        )
+    }
+}
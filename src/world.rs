@@ -0,0 +1,431 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use bean_script::util::{make_ref, MutRc};
+use itertools::Itertools;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use raylib::prelude::*;
+
+use crate::{
+    assets::Assets,
+    bullet::BulletPool,
+    collision::{CollisionFrame, CollisionLayer},
+    object::Object,
+    replay::{Recorder, Snapshot},
+    rock::Rock,
+    rules::Rules,
+    ship::{Event, Ship},
+};
+
+struct KillFeedEntry {
+    text: String,
+    tick: u64,
+}
+
+pub struct World {
+    pub ships: Vec<Ship>,
+    rocks: Vec<Rock>,
+    bullet_pool: MutRc<BulletPool>,
+    tick: u64,
+    seed: u64,
+    recorder: Option<Recorder>,
+    history: VecDeque<Snapshot>,
+    history_length: usize,
+    kill_feed: Vec<KillFeedEntry>,
+}
+
+impl World {
+    /// Length of one simulation step. The world only ever advances by this much so that
+    /// matches play out the same regardless of frame rate.
+    pub const TICK: f32 = 1.0 / 60.0;
+    /// Seconds a kill stays in the on-screen feed.
+    const KILL_FEED_TIME: f32 = 5.0;
+    /// How long dropping the world waits for script threads to finish.
+    const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub fn new(paths: &[String], seed: u64, rules: &Rules) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let bullet_pool = make_ref(BulletPool::new(60, rules.friendly_fire));
+
+        let mut positions: Vec<(f32, f32)> = Vec::new();
+        let ships: Vec<Ship> = make_ships(paths, &mut positions, &bullet_pool, rules, &mut rng);
+        let mut rocks: Vec<Rock> = make_rocks(&mut positions, &mut rng);
+
+        let first_id = rocks.len();
+        rocks.extend(
+            ships
+                .iter()
+                .combinations(2)
+                .flat_map(|pair| {
+                    let [s1, s2] = &pair[..] else {
+                        return Vec::new();
+                    };
+                    let pos = s1.get_pos() + (s2.get_pos() - s1.get_pos()) * 0.5;
+
+                    if positions
+                        .iter()
+                        .any(|(x, y)| (pos.x - x).abs() + (pos.y - y).abs() < 150.0)
+                    {
+                        Vec::new()
+                    } else {
+                        vec![pos]
+                    }
+                })
+                .enumerate()
+                .map(|(i, pos)| Rock::new(first_id + i, pos.x, pos.y)),
+        );
+
+        Self {
+            ships,
+            rocks,
+            bullet_pool,
+            tick: 0,
+            seed,
+            recorder: None,
+            history: VecDeque::new(),
+            history_length: 0,
+            kill_feed: Vec::new(),
+        }
+    }
+
+    pub fn step(&mut self) {
+        let mut collision_frame = CollisionFrame::new(vec![
+            ("ship", CollisionLayer::from(&self.ships)),
+            ("bullet", self.bullet_pool.borrow_mut().collision_layer()),
+            ("rock", CollisionLayer::from(&self.rocks)),
+        ]);
+        // Bullets are the only thing that gets used up by hitting something, so working out
+        // what each one hit up front keeps ships, rocks and the bullets themselves agreeing.
+        let bullet_pool = self.bullet_pool.borrow();
+        collision_frame.resolve_contacts(
+            "bullet",
+            vec!["ship", "rock", "bullet", "wall"],
+            |bullet, other| bullet_pool.can_hit(bullet, other),
+        );
+        drop(bullet_pool);
+
+        let was_alive: Vec<bool> = self.ships.iter().map(|s| !s.is_destroyed()).collect();
+        let was_running: Vec<bool> = self.ships.iter().map(|s| s.error().is_none()).collect();
+
+        self.ships.update(Self::TICK, &collision_frame);
+        // Ships move during their update, so what they ran into can only be worked out after.
+        // Rocks and walls are resolved separately, so a ship that hit a rock is still stopped
+        // by a wall.
+        collision_frame.replace_layer("ship", CollisionLayer::from(&self.ships));
+        collision_frame.resolve_contacts("ship", vec!["rock"], |_, _| true);
+        collision_frame.resolve_contacts("ship", vec!["wall"], |_, _| true);
+        for ship in self.ships.iter_mut() {
+            ship.resolve_contacts(&collision_frame);
+        }
+        self.rocks.update(Self::TICK, &collision_frame);
+        self.bullet_pool
+            .borrow_mut()
+            .update(Self::TICK, &collision_frame);
+
+        for (id, (was_alive, was_running)) in was_alive.into_iter().zip(was_running).enumerate() {
+            if was_running && self.ships[id].error().is_some() {
+                self.kill_feed.push(KillFeedEntry {
+                    text: format!("{}'s script crashed", self.ships[id].name),
+                    tick: self.tick,
+                });
+            }
+            if was_alive && self.ships[id].is_destroyed() {
+                self.report_destroyed(id);
+            }
+        }
+
+        self.tick += 1;
+        self.record_snapshot();
+    }
+
+    fn report_destroyed(&mut self, id: usize) {
+        let victim = &self.ships[id].name;
+        let text = match self.ships[id].killed_by() {
+            Some(killer) if killer == id => format!("{} destroyed itself", victim),
+            Some(killer) => format!("{} destroyed {}", self.ships[killer].name, victim),
+            None => format!("{} crashed into a rock", victim),
+        };
+        self.kill_feed.push(KillFeedEntry {
+            text,
+            tick: self.tick,
+        });
+
+        if let Some(killer) = self.ships[id].killed_by().filter(|&killer| killer != id) {
+            self.ships[killer].add_kill();
+        }
+
+        let name = self.ships[id].name.clone();
+        for ship in self.ships.iter_mut().filter(|ship| ship.get_id() != id) {
+            ship.notify(Event::ShipDestroyed(name.clone()));
+        }
+    }
+
+    /// Writes the current state and every following tick to `recorder`.
+    pub fn record(&mut self, mut recorder: Recorder) {
+        match recorder.record(&self.snapshot()) {
+            Ok(()) => self.recorder = Some(recorder),
+            Err(error) => println!("\x1b[31;1merror\x1b[0m: Failed to write replay: {}", error),
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(error) = recorder.finish() {
+                println!("\x1b[31;1merror\x1b[0m: Failed to write replay: {}", error);
+            }
+        }
+    }
+
+    /// Keeps snapshots of the last `length` ticks around so they can be rewound through.
+    pub fn keep_history(&mut self, length: usize) {
+        self.history_length = length;
+        self.history.clear();
+        self.history.push_back(self.snapshot());
+    }
+
+    /// Snapshots of recent ticks, oldest first. The last one is always the current tick.
+    pub fn history(&self) -> &VecDeque<Snapshot> {
+        &self.history
+    }
+
+    fn record_snapshot(&mut self) {
+        if self.recorder.is_none() && self.history_length == 0 {
+            return;
+        }
+        let snapshot = self.snapshot();
+
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(error) = recorder.record(&snapshot) {
+                println!("\x1b[31;1merror\x1b[0m: Failed to write replay: {}", error);
+                self.recorder = None;
+            }
+        }
+
+        if self.history_length > 0 {
+            if self.history.len() == self.history_length {
+                self.history.pop_front();
+            }
+            self.history.push_back(snapshot);
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tick: self.tick,
+            ships: self.ships.iter().map(Ship::snapshot).collect(),
+            bullets: self.bullet_pool.borrow().snapshot(),
+            rocks: self.rocks.iter().map(Rock::snapshot).collect(),
+        }
+    }
+
+    /// Steps the world as fast as the scripts keep up until there's a winner or `time_limit`
+    /// simulated seconds have passed. Only meant for lockstep matches, where each step waits
+    /// for the scripts.
+    pub fn play_out(&mut self, time_limit: f32) {
+        while !self.is_over() && self.time() < time_limit {
+            self.step();
+        }
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, assets: &Assets, alpha: f32) {
+        self.rocks.draw(d, assets, alpha);
+        self.ships.draw(d, assets, alpha);
+        self.bullet_pool.borrow().draw(d, assets, alpha);
+
+        let mut y = 10;
+        for ship in &self.ships {
+            d.draw_text(
+                &format!("{}: {} kills", ship.name, ship.kills()),
+                10,
+                y,
+                18,
+                Color::GREEN,
+            );
+            y += 20;
+            if let Some(error) = ship.error() {
+                d.draw_text(&format!("crashed: {}", error), 20, y, 14, Color::RED);
+                y += 18;
+            }
+        }
+
+        let recent = self.kill_feed.iter().rev().take_while(|entry| {
+            (self.tick - entry.tick) as f32 * Self::TICK < Self::KILL_FEED_TIME
+        });
+        for (i, entry) in recent.enumerate() {
+            d.draw_text(
+                &entry.text,
+                1270 - text::measure_text(&entry.text, 18),
+                10 + i as i32 * 20,
+                18,
+                Color::RED,
+            );
+        }
+    }
+
+    /// Lines describing scripts that crashed or ran over their step budget, for match results.
+    pub fn incidents(&self) -> Vec<String> {
+        let mut incidents = Vec::new();
+        for ship in &self.ships {
+            if let Some(error) = ship.error() {
+                incidents.push(format!("{}'s script crashed: {}", ship.name, error));
+            }
+            if ship.throttled() > 0 {
+                incidents.push(format!(
+                    "{}'s script was throttled {} times for running over its step budget",
+                    ship.name,
+                    ship.throttled()
+                ));
+            }
+        }
+        incidents
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.ships.iter().map(|ship| ship.name.clone()).collect()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Simulated time in seconds since the match started.
+    pub fn time(&self) -> f32 {
+        self.tick as f32 * Self::TICK
+    }
+
+    pub fn alive(&self) -> impl Iterator<Item = &Ship> {
+        self.ships.iter().filter(|s| !s.is_eliminated())
+    }
+
+    /// The last ship standing, if there is exactly one.
+    pub fn winner(&self) -> Option<&Ship> {
+        self.alive().exactly_one().ok()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.alive().count() <= 1
+    }
+}
+
+impl Drop for World {
+    fn drop(&mut self) {
+        for ship in &self.ships {
+            ship.stop();
+        }
+
+        let deadline = Instant::now() + Self::SHUTDOWN_TIMEOUT;
+        for ship in self.ships.drain(..) {
+            ship.join(deadline);
+        }
+    }
+}
+
+fn random_position(rng: &mut StdRng) -> (f32, f32) {
+    (
+        rng.gen_range(80..=1200) as f32,
+        rng.gen_range(80..=880) as f32,
+    )
+}
+
+fn make_rocks(positions: &mut Vec<(f32, f32)>, rng: &mut StdRng) -> Vec<Rock> {
+    (0..rng.gen_range(13..=16))
+        .map(|id| {
+            let mut pos = random_position(rng);
+            while positions
+                .iter()
+                .any(|(x, y)| (pos.0 - x).abs() + (pos.1 - y).abs() < 200.0)
+            {
+                pos = random_position(rng);
+            }
+
+            positions.push(pos);
+            Rock::new(id, pos.0, pos.1)
+        })
+        .collect()
+}
+
+fn make_ships(
+    paths: &[String],
+    positions: &mut Vec<(f32, f32)>,
+    bullet_pool: &MutRc<BulletPool>,
+    rules: &Rules,
+    rng: &mut StdRng,
+) -> Vec<Ship> {
+    paths
+        .iter()
+        .enumerate()
+        .map(|(id, p)| {
+            let mut pos = random_position(rng);
+            while positions
+                .iter()
+                .any(|(x, y)| (pos.0 - x).abs() + (pos.1 - y).abs() < 200.0)
+            {
+                pos = random_position(rng);
+            }
+
+            positions.push(pos);
+            Ship::new(
+                id,
+                p.clone(),
+                bullet_pool.clone(),
+                rules.clone(),
+                pos.0,
+                pos.1,
+                rng.gen(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use super::*;
+
+    /// Runs `source` as the only bot in a lockstep match and returns the tick it starts
+    /// turning on.
+    fn ticks_until_turning(name: &str, source: &str) -> u64 {
+        let dir = std::env::temp_dir().join(format!("world-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bot = dir.join(format!("{}.bean", name));
+        fs::write(&bot, source).unwrap();
+
+        let rules = Rules {
+            // Long enough that a slow test host can't make the script miss a tick. Ticks
+            // end as soon as the script answers, so it doesn't slow the test down.
+            lockstep: Some(Duration::from_secs(5)),
+            ..Rules::default()
+        };
+        let mut world = World::new(&[bot.to_string_lossy().into_owned()], 1, &rules);
+        while world.snapshot().ships[0].rotation == 0.0 {
+            assert!(world.tick < 1000, "the bot never turned");
+            world.step();
+        }
+        world.tick
+    }
+
+    #[test]
+    fn wait_takes_exact_ticks() {
+        let source = "
+            use(\"robot_api\", <robot>)
+            repeat(10): { robot.wait(10) }
+            robot.turn(90)
+        ";
+        // Ten waits of ten ticks, then the turn takes a tick of its own.
+        assert_eq!(ticks_until_turning("wait", source), 10 * 10 + 1);
+    }
+
+    #[test]
+    fn lockstep_sleep_takes_exact_ticks() {
+        let source = "
+            use(\"robot_api\", <robot>)
+            repeat(4): { sleep(250) }
+            robot.turn(90)
+        ";
+        // 250ms is 15 ticks.
+        assert_eq!(ticks_until_turning("sleep", source), 4 * 15 + 1);
+    }
+}